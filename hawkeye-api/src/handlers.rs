@@ -1,12 +1,12 @@
 use crate::config::NAMESPACE;
 use crate::templates;
-use hawkeye_core::models::{Status, Watcher};
+use hawkeye_core::models::{Secret, SecretRef, Status, Watcher};
 use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::{ConfigMap, Pod, Service};
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret as KubeSecret, Service};
 use kube::api::{DeleteParams, ListParams, PatchParams, PostParams};
 use kube::{Api, Client};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
 use uuid::Uuid;
 use warp::http::header::{CONTENT_TYPE, CACHE_CONTROL};
@@ -46,6 +46,7 @@ pub async fn list_watchers(client: Client) -> Result<impl warp::Reply, Infallibl
         w.status = Some(calculated_status);
         // TODO: Comes from the service
         w.source.ingest_ip = None;
+        w.redact_secrets();
         watchers.push(w);
     }
 
@@ -58,11 +59,52 @@ pub async fn create_watcher(
 ) -> Result<impl warp::Reply, Infallible> {
     log::debug!("v1.create_watcher: {:?}", watcher);
 
+    if let Err(err) = watcher.is_valid() {
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "message": format!("Invalid watcher: {}", err)
+            })),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
     let new_id = Uuid::new_v4().to_string();
     watcher.id = Some(new_id.clone());
     let pp = PostParams::default();
 
-    // 1. Create ConfigMap
+    // 1. Move the credentials written inline to a Secret, the watcher only keeps references
+    let secret_name = templates::secret_name(&new_id);
+    let mut secret_data = BTreeMap::new();
+    let mut secret_names = Vec::new();
+    for (index, secret) in watcher.secrets_mut().into_iter().enumerate() {
+        if secret.is_inline() {
+            let key = format!("secret-{}", index);
+            let value = std::mem::replace(
+                secret,
+                Secret::Reference {
+                    secret_ref: SecretRef {
+                        name: secret_name.clone(),
+                        key: key.clone(),
+                    },
+                },
+            );
+            secret_data.insert(key, value.expose().unwrap().to_string());
+        }
+        if let Secret::Reference { secret_ref } = secret {
+            if !secret_names.contains(&secret_ref.name) {
+                secret_names.push(secret_ref.name.clone());
+            }
+        }
+    }
+    if !secret_data.is_empty() {
+        log::debug!("Creating Secret instance");
+        let secrets: Api<KubeSecret> = Api::namespaced(client.clone(), &NAMESPACE);
+        let secret = templates::build_secret(&new_id, &secret_data);
+        // TODO: Handle errors
+        let _ = secrets.create(&pp, &secret).await.unwrap();
+    }
+
+    // 2. Create ConfigMap
     log::debug!("Creating ConfigMap instance");
     let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
     let config_file_contents = serde_json::to_string(&watcher).unwrap();
@@ -70,22 +112,24 @@ pub async fn create_watcher(
     // TODO: Handle errors
     let _ = config_maps.create(&pp, &config).await.unwrap();
 
-    // 2. Create Deployment with replicas=0
+    // 3. Create Deployment with replicas=0
     log::debug!("Creating Deployment instance");
     let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let deploy = templates::build_deployment(&new_id, watcher.source.ingest_port);
+    let deploy = templates::build_deployment(&new_id, &watcher.source, &secret_names);
     // TODO: Handle errors
     let _ = deployments.create(&pp, &deploy).await.unwrap();
 
-    // 3. Create Service/LoadBalancer
-    log::debug!("Creating Service instance");
-    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
-    let svc = templates::build_service(&new_id, watcher.source.ingest_port);
-    // TODO: Handle errors
-    let _ = services.create(&pp, &svc).await.unwrap();
+    // 4. Create Service/LoadBalancer, only needed when the video feed is pushed to the worker
+    if let Some(svc) = templates::build_service(&new_id, &watcher.source) {
+        log::debug!("Creating Service instance");
+        let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+        // TODO: Handle errors
+        let _ = services.create(&pp, &svc).await.unwrap();
+    }
 
     watcher.status = Some(Status::Pending);
     watcher.source.ingest_ip = None;
+    watcher.redact_secrets();
 
     Ok(reply::with_status(
         reply::json(&watcher),
@@ -127,6 +171,7 @@ pub async fn get_watcher(id: String, client: Client) -> Result<impl warp::Reply,
     let mut w: Watcher =
         serde_json::from_str(config_map.data.unwrap().get("watcher.json").unwrap()).unwrap();
     w.status = Some(deployment.get_watcher_status());
+    w.redact_secrets();
 
     w.status_description = if let Some(Status::Pending) = w.status.as_ref() {
         // Load more information why it's in pending status
@@ -159,7 +204,7 @@ pub async fn get_watcher(id: String, client: Client) -> Result<impl warp::Reply,
     };
 
     // Comes from the service
-    w.source.ingest_ip = if w.status != Some(Status::Error) {
+    w.source.ingest_ip = if w.status != Some(Status::Error) && w.source.listen_port().is_some() {
         log::debug!("Getting ingest_ip from Service's LoadBalancer");
         let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
         let service = services
@@ -400,13 +445,19 @@ pub async fn delete_watcher(id: String, client: Client) -> Result<impl warp::Rep
         .delete(&templates::deployment_name(&id), &dp)
         .await;
 
-    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let _ = config_maps
-        .delete(&templates::configmap_name(&id), &dp)
-        .await;
+    // Not every watcher has a service, e.g. when the worker connects to the video source
+    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+    let _ = services.delete(&templates::service_name(&id), &dp).await;
+
+    // Only watchers created with inline credentials have a secret
+    let secrets: Api<KubeSecret> = Api::namespaced(client.clone(), &NAMESPACE);
+    let _ = secrets.delete(&templates::secret_name(&id), &dp).await;
 
-    let services: Api<Service> = Api::namespaced(client, &NAMESPACE);
-    match services.delete(&templates::service_name(&id), &dp).await {
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    match config_maps
+        .delete(&templates::configmap_name(&id), &dp)
+        .await
+    {
         Ok(_) => Ok(reply::with_status(
             reply::json(&json!({
                 "message": "Watcher has been deleted"
@@ -1,8 +1,9 @@
 use crate::config::DOCKER_IMAGE;
-use hawkeye_core::models::Status;
+use hawkeye_core::models::{Source, Status};
 use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::{ConfigMap, Service};
+use k8s_openapi::api::core::v1::{ConfigMap, Secret, Service};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 /// Builds an idempotent name for the `ConfigMap` based on the `watcher_id`.
 pub fn configmap_name(watcher_id: &str) -> String {
@@ -29,6 +30,29 @@ pub fn build_configmap(watcher_id: &str, contents: &str) -> ConfigMap {
     .unwrap()
 }
 
+/// Builds an idempotent name for the `Secret` based on the `watcher_id`.
+pub fn secret_name(watcher_id: &str) -> String {
+    format!("hawkeye-secret-{}", watcher_id)
+}
+
+/// Builds a `Secret` holding the credentials that were written inline in the watcher.
+pub fn build_secret(watcher_id: &str, data: &BTreeMap<String, String>) -> Secret {
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": secret_name(watcher_id),
+            "labels": {
+                "app": "hawkeye",
+                "watcher_id": watcher_id,
+            }
+        },
+        "type": "Opaque",
+        "stringData": data
+    }))
+    .unwrap()
+}
+
 /// Builds an idempotent name for the `Deployment` based on the `watcher_id`.
 pub fn deployment_name(watcher_id: &str) -> String {
     format!("hawkeye-deploy-{}", watcher_id)
@@ -39,9 +63,64 @@ pub fn deployment_metrics_port() -> u32 {
     3030
 }
 
+/// Builds the container ports of the hawkeye-worker, the video feed port is only exposed when the
+/// worker receives the feed instead of connecting to it.
+fn container_ports(source: &Source) -> serde_json::Value {
+    let mut ports = Vec::new();
+    if let Some(ingest_port) = source.listen_port() {
+        ports.push(json!({
+            "containerPort": ingest_port,
+            "protocol": "UDP"
+        }));
+    }
+    ports.push(json!({
+        "containerPort": deployment_metrics_port(),
+        "protocol": "TCP"
+    }));
+    json!(ports)
+}
+
+/// Builds the volumes of the hawkeye-worker, each `Secret` referenced by the watcher is mounted
+/// in its own directory under `/secrets`.
+fn volumes(watcher_id: &str, secret_names: &[String]) -> (serde_json::Value, serde_json::Value) {
+    let mut volumes = vec![json!({
+        "name": "config",
+        "configMap": {
+            "name": configmap_name(watcher_id),
+            "items": [
+                {
+                    "key": "watcher.json",
+                    "path": "watcher.json"
+                }
+            ]
+        }
+    })];
+    let mut mounts = vec![json!({
+        "mountPath": "/config",
+        "name": "config",
+        "readOnly": true
+    })];
+    for (index, name) in secret_names.iter().enumerate() {
+        let volume_name = format!("secret-{}", index);
+        volumes.push(json!({
+            "name": volume_name,
+            "secret": {
+                "secretName": name
+            }
+        }));
+        mounts.push(json!({
+            "mountPath": format!("/secrets/{}", name),
+            "name": volume_name,
+            "readOnly": true
+        }));
+    }
+    (json!(volumes), json!(mounts))
+}
+
 /// Builds a `Deployment` configured to run the hawkeye-worker process.
-pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
+pub fn build_deployment(watcher_id: &str, source: &Source, secret_names: &[String]) -> Deployment {
     let metric_port_str = deployment_metrics_port().to_string();
+    let (volumes, volume_mounts) = volumes(watcher_id, secret_names);
     serde_json::from_value(json!({
         "apiVersion": "apps/v1",
         "kind": "Deployment",
@@ -109,39 +188,11 @@ pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
                                     "memory": "50Mi"
                                 }
                             },
-                            "ports": [
-                                {
-                                    "containerPort": ingest_port,
-                                    "protocol": "UDP"
-                                },
-                                {
-                                    "containerPort": deployment_metrics_port(),
-                                    "protocol": "TCP"
-                                }
-                            ],
-                            "volumeMounts": [
-                                {
-                                    "mountPath": "/config",
-                                    "name": "config",
-                                    "readOnly": true
-                                }
-                            ]
+                            "ports": container_ports(source),
+                            "volumeMounts": volume_mounts
                         }
                     ],
-                    "volumes": [
-                        {
-                            "name": "config",
-                            "configMap": {
-                                "name": configmap_name(watcher_id),
-                                "items": [
-                                    {
-                                        "key": "watcher.json",
-                                        "path": "watcher.json"
-                                    }
-                                ]
-                            }
-                        }
-                    ]
+                    "volumes": volumes
                 }
             }
         }
@@ -155,8 +206,12 @@ pub fn service_name(watcher_id: &str) -> String {
 }
 
 /// Builds a `Service` in the format expected to expose the hawkeye-worker.
-pub fn build_service(watcher_id: &str, ingest_port: u32) -> Service {
-    serde_json::from_value(json!({
+///
+/// Both RTP and SRT are carried over UDP. Sources where the worker connects to the remote end
+/// (e.g. SRT in caller mode) don't need to be exposed, so no `Service` is built for them.
+pub fn build_service(watcher_id: &str, source: &Source) -> Option<Service> {
+    let ingest_port = source.listen_port()?;
+    let service = serde_json::from_value(json!({
         "apiVersion": "v1",
         "kind": "Service",
         "metadata": {
@@ -187,5 +242,6 @@ pub fn build_service(watcher_id: &str, ingest_port: u32) -> Service {
             ]
         }
     }))
-    .unwrap()
+    .unwrap();
+    Some(service)
 }
@@ -0,0 +1,77 @@
+//! JSONPath selecting a value of a JSON document. Only child (`.key`, `['key']`) and index (`[0]`)
+//! selectors are supported.
+
+use color_eyre::{eyre::eyre, Result};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Selector {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses the selectors of the JSONPath, starting at the root `$`.
+pub fn parse(path: &str) -> Result<Vec<Selector>> {
+    let invalid = || eyre!("Unsupported JSONPath: {}", path);
+    let path = path.trim();
+    if !path.starts_with('$') {
+        return Err(invalid());
+    }
+    let mut rest = &path[1..];
+    let mut selectors = Vec::new();
+    while !rest.is_empty() {
+        if rest.starts_with('.') {
+            rest = &rest[1..];
+            let end = rest.find(|c| c == '.' || c == '[').unwrap_or(rest.len());
+            let key = &rest[..end];
+            if key.is_empty() {
+                return Err(invalid());
+            }
+            rest = &rest[end..];
+            selectors.push(Selector::Key(key.to_string()));
+        } else if rest.starts_with('[') {
+            let end = rest.find(']').ok_or_else(invalid)?;
+            let selector = rest[1..end].trim();
+            rest = &rest[end + 1..];
+            if selector.len() >= 2
+                && (selector.starts_with('\'') && selector.ends_with('\'')
+                    || selector.starts_with('"') && selector.ends_with('"'))
+            {
+                selectors.push(Selector::Key(selector[1..selector.len() - 1].to_string()));
+            } else {
+                let index: usize = selector.parse().map_err(|_| invalid())?;
+                selectors.push(Selector::Index(index));
+            }
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(selectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selectors() {
+        assert_eq!(
+            parse("$.result['a.b'][1].id").unwrap(),
+            vec![
+                Selector::Key("result".to_string()),
+                Selector::Key("a.b".to_string()),
+                Selector::Index(1),
+                Selector::Key("id".to_string()),
+            ]
+        );
+        assert_eq!(parse(" $ ").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parse_rejects_unsupported_paths() {
+        assert!(parse("result").is_err());
+        assert!(parse("$.").is_err());
+        assert!(parse("$.result[x]").is_err());
+        assert!(parse("$.result[0").is_err());
+        assert!(parse("$..id").is_err());
+    }
+}
@@ -1 +1,2 @@
+pub mod json_path;
 pub mod models;
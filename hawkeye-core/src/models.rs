@@ -1,11 +1,14 @@
+use crate::json_path;
 use color_eyre::eyre::WrapErr;
 use color_eyre::{eyre::eyre, Result};
 use log::debug;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::time::Duration;
 
 #[skip_serializing_none]
@@ -13,29 +16,214 @@ use std::time::Duration;
 pub struct Watcher {
     pub id: Option<String>,
     pub description: Option<String>,
-    pub slate_url: String,
+    /// Single slate image, kept for watchers created before `slates` was available.
+    pub slate_url: Option<String>,
+    #[serde(default)]
+    pub slates: Vec<Slate>,
     pub status: Option<Status>,
     pub status_description: Option<String>,
     pub source: Source,
+    pub detector: Option<DetectorSettings>,
+    pub debounce: Option<Debounce>,
+    pub audio: Option<AudioDetection>,
+    pub scte35: Option<Scte35Detection>,
+    pub rules: Option<Vec<ModeRule>>,
     pub transitions: Vec<Transition>,
 }
 
 impl Watcher {
     pub fn is_valid(&self) -> Result<()> {
-        if self.slate_url.starts_with("http://")
-            || self.slate_url.starts_with("https://")
-            || self.slate_url.starts_with("file://")
+        let slates = self.reference_slates();
+        let detects_without_slates = matches!(
+            self.detector.as_ref(),
+            Some(detector) if detector.black_frame.is_some() || detector.frozen_frame.is_some()
+        );
+        if slates.is_empty() && !detects_without_slates {
+            return Err(eyre!(
+                "At least one slate, or the black or frozen frame detection, is required!"
+            ));
+        }
+        for slate in slates.iter() {
+            slate.is_valid()?;
+        }
+        if let Some(detector) = self.detector.as_ref() {
+            detector.is_valid()?;
+        }
+        if self.audio.is_some() && self.source.container == Container::RawVideo {
+            return Err(eyre!("Audio detection requires a container with the audio"));
+        }
+        if self.scte35.is_some() && self.source.container != Container::MpegTs {
+            return Err(eyre!("SCTE-35 detection requires the mpeg-ts container"));
+        }
+        let rules = self.rules.as_deref().unwrap_or_default();
+        for (index, rule) in rules.iter().enumerate() {
+            if rules[..index].iter().any(|other| other.name == rule.name) {
+                return Err(eyre!("Mode rule {} is defined twice", rule.name));
+            }
+            // Transitions would reference the video mode instead
+            let name = serde_json::Value::String(rule.name.clone());
+            if rule.name.is_empty() || serde_json::from_value::<VideoMode>(name).is_ok() {
+                return Err(eyre!("Mode rule name {:?} is not valid", rule.name));
+            }
+            rule.condition.is_valid()?;
+            for mode in rule.condition.modes() {
+                self.check_detected(mode)?;
+            }
+        }
+        for transition in self.transitions.iter() {
+            if transition.to == VideoMode::Unknown {
+                return Err(eyre!("Transitions can't enter the unknown mode"));
+            }
+            if transition.from != VideoMode::Unknown
+                && transition.from.kind() != transition.to.kind()
+            {
+                return Err(eyre!(
+                    "Transitions can't change between audio, video and rule modes"
+                ));
+            }
+            if matches!(transition.audio, Some(mode) if mode.kind() != ModeKind::Audio) {
+                return Err(eyre!("Transition audio must be silence or audio"));
+            }
+            for step in transition.actions.iter() {
+                for action in step.actions() {
+                    if let Action::HttpCall(HttpCall {
+                        expected_body: Some(assertion),
+                        ..
+                    }) = action
+                    {
+                        assertion.is_valid()?;
+                    }
+                }
+            }
+            for mode in [&transition.from, &transition.to].iter() {
+                match mode {
+                    Mode::Video(mode) => self.check_detected(*mode)?,
+                    Mode::Rule(name) => {
+                        if !rules.iter().any(|rule| &rule.name == name) {
+                            return Err(eyre!("Transition references unknown mode {}", name));
+                        }
+                    }
+                }
+            }
+            if let Some(audio) = transition.audio {
+                self.check_detected(audio)?;
+            }
+        }
+        self.source.is_valid()
+    }
+
+    /// Checks the watcher is configured to detect the mode.
+    fn check_detected(&self, mode: VideoMode) -> Result<()> {
+        if mode.kind() == ModeKind::Audio && self.audio.is_none() {
+            return Err(eyre!("The {} mode requires audio detection", mode.name()));
+        }
+        if mode.kind() == ModeKind::Cue && self.scte35.is_none() {
+            return Err(eyre!("The {} mode requires SCTE-35 detection", mode.name()));
+        }
+        if mode == VideoMode::NoSignal && self.source.no_signal_timeout.is_none() {
+            return Err(eyre!(
+                "The no_signal mode requires a source no_signal_timeout"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Credentials used by the source and by the actions.
+    pub fn secrets_mut(&mut self) -> Vec<&mut Secret> {
+        let mut secrets = Vec::new();
+        if let Protocol::Srt {
+            passphrase: Some(passphrase),
+            ..
+        } = &mut self.source.transport
+        {
+            secrets.push(passphrase);
+        }
+        for transition in self.transitions.iter_mut() {
+            for step in transition.actions.iter_mut() {
+                for action in step.actions_mut() {
+                    match action {
+                        Action::HttpCall(call) => {
+                            if let Some(authorization) = call.authorization.as_mut() {
+                                secrets.push(authorization.secret_mut());
+                            }
+                            secrets.extend(call.headers.iter_mut().flat_map(|h| h.values_mut()));
+                        }
+                        Action::Exec(exec) => {
+                            secrets.extend(exec.env.iter_mut().flat_map(|env| env.values_mut()));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        secrets
+    }
+
+    /// Replaces the references to credentials with their values.
+    pub fn resolve_secrets(&mut self, secrets_path: &Path) -> Result<()> {
+        for secret in self.secrets_mut() {
+            *secret = Secret::Value(secret.resolve(secrets_path)?);
+        }
+        Ok(())
+    }
+
+    /// Hides the credentials written in the watcher.
+    pub fn redact_secrets(&mut self) {
+        for secret in self.secrets_mut() {
+            secret.redact();
+        }
+    }
+
+    /// All the slates the video frames are compared with.
+    pub fn reference_slates(&self) -> Vec<Slate> {
+        let mut slates = self.slates.clone();
+        if let Some(url) = self.slate_url.as_ref() {
+            slates.insert(
+                0,
+                Slate {
+                    url: url.clone(),
+                    label: None,
+                    threshold: None,
+                    regions: None,
+                },
+            );
+        }
+        slates
+    }
+}
+
+/// A reference image of a slate that may be present in the video stream.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Slate {
+    pub url: String,
+    /// Identifies which slate was found, so transitions can be restricted to it.
+    pub label: Option<String>,
+    /// Overrides the detector threshold for this slate.
+    pub threshold: Option<u32>,
+    /// Areas of the frame to include in, or exclude from, the comparison with this slate.
+    pub regions: Option<Vec<Region>>,
+}
+
+impl Slate {
+    fn is_valid(&self) -> Result<()> {
+        if self.url.starts_with("http://")
+            || self.url.starts_with("https://")
+            || self.url.starts_with("file://")
         {
-            Ok(self.source.is_valid()?)
+            for region in self.regions.iter().flatten() {
+                region.is_valid()?;
+            }
+            Ok(())
         } else {
-            Err(eyre!("{} not recognized as a valid URL!", self.slate_url))
+            Err(eyre!("{} not recognized as a valid URL!", self.url))
         }
     }
 
-    pub fn slate(&self) -> Result<Box<dyn Read>> {
-        if self.slate_url.starts_with("http://") || self.slate_url.starts_with("https://") {
+    pub fn load(&self) -> Result<Box<dyn Read>> {
+        if self.url.starts_with("http://") || self.url.starts_with("https://") {
             debug!("Loading slate from url");
-            let res = ureq::get(self.slate_url.as_str())
+            let res = ureq::get(self.url.as_str())
                 .timeout(Duration::from_secs(10))
                 .timeout_connect(500)
                 .call();
@@ -43,13 +231,13 @@ impl Watcher {
                 return Err(color_eyre::eyre::eyre!(
                     "HTTP error ({}) while calling URL of backend: {}",
                     res.status(),
-                    self.slate_url
+                    self.url
                 ));
             }
             Ok(Box::new(res.into_reader()))
         } else {
             debug!("Loading slate from file");
-            let path = self.slate_url.replace("file://", "");
+            let path = self.url.replace("file://", "");
             Ok(Box::new(
                 File::open(path).wrap_err("Could not open slate file")?,
             ))
@@ -70,21 +258,63 @@ pub enum Status {
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Source {
     pub ingest_ip: Option<String>,
-    pub ingest_port: u32,
+    pub ingest_port: Option<u32>,
     pub container: Container,
     pub codec: Codec,
     pub transport: Protocol,
+    /// Milliseconds without frames before the video is in the `no_signal` mode, never entered
+    /// when not set.
+    pub no_signal_timeout: Option<u32>,
 }
 
 impl Source {
     fn is_valid(&self) -> Result<()> {
-        if self.ingest_port > 1024 && self.ingest_port < 60_000 {
-            Ok(())
-        } else {
-            Err(eyre!(
+        self.transport.is_valid()?;
+        self.is_supported()?;
+        if !self.transport.is_push() {
+            return Ok(());
+        }
+        match self.ingest_port {
+            Some(port) if port > 1024 && port < 60_000 => Ok(()),
+            Some(port) => Err(eyre!(
                 "Source port {} is not in within the valid range (1024-60000)",
-                self.ingest_port
-            ))
+                port
+            )),
+            None => Err(eyre!(
+                "Source port is required for the {} transport",
+                self.transport.name()
+            )),
+        }
+    }
+
+    /// Checks the worker is able to build a pipeline for the combination of transport, container
+    /// and codec.
+    fn is_supported(&self) -> Result<()> {
+        match (&self.transport, self.container, self.codec) {
+            (Protocol::Rtp, Container::MpegTs, _)
+            | (Protocol::Rtp, Container::RawVideo, _)
+            | (Protocol::Srt { .. }, Container::MpegTs, _)
+            | (Protocol::Hls { .. }, Container::MpegTs, _)
+            | (Protocol::Hls { .. }, Container::Fmp4, _)
+            | (Protocol::Http { .. }, Container::MpegTs, _)
+            | (Protocol::Http { .. }, Container::Fmp4, _)
+            | (Protocol::File { .. }, Container::MpegTs, _)
+            | (Protocol::File { .. }, Container::Fmp4, _) => Ok(()),
+            (transport, container, codec) => Err(eyre!(
+                "Container ({:?}) and Codec ({:?}) not available for the {} transport",
+                container,
+                codec,
+                transport.name()
+            )),
+        }
+    }
+
+    /// The port the worker listens on for the incoming video feed, if the transport requires one.
+    pub fn listen_port(&self) -> Option<u32> {
+        if self.transport.is_push() {
+            self.ingest_port
+        } else {
+            None
         }
     }
 }
@@ -104,30 +334,551 @@ pub enum Codec {
     H265,
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(tag = "protocol", rename_all = "lowercase")]
 pub enum Protocol {
     Rtp,
+    Srt {
+        mode: SrtMode,
+        /// Remote `host:port` to connect to, required in caller mode.
+        address: Option<String>,
+        /// Encrypts the stream, a secret like the credentials of the actions.
+        passphrase: Option<Secret>,
+        /// Receiver latency in milliseconds.
+        latency: Option<u32>,
+    },
+    /// Pulls the video from an HLS (or LL-HLS) playlist instead of receiving a feed.
+    Hls {
+        url: String,
+    },
+    /// Pulls the video from an HTTP URL, e.g. a chunked transfer of a CMAF stream.
+    Http {
+        url: String,
+    },
+    /// Reads the video from a file available to the worker.
+    File {
+        path: String,
+    },
+}
+
+impl Protocol {
+    /// Returns `true` when the video feed is pushed to the worker on `Source.ingest_port`.
+    pub fn is_push(&self) -> bool {
+        match self {
+            Protocol::Rtp => true,
+            Protocol::Srt { mode, .. } => *mode == SrtMode::Listener,
+            Protocol::Hls { .. } | Protocol::Http { .. } | Protocol::File { .. } => false,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Rtp => "rtp",
+            Protocol::Srt { .. } => "srt",
+            Protocol::Hls { .. } => "hls",
+            Protocol::Http { .. } => "http",
+            Protocol::File { .. } => "file",
+        }
+    }
+
+    fn is_valid(&self) -> Result<()> {
+        match self {
+            Protocol::Rtp => Ok(()),
+            Protocol::Srt {
+                mode,
+                address,
+                passphrase,
+                ..
+            } => {
+                if *mode == SrtMode::Caller && address.is_none() {
+                    return Err(eyre!("SRT caller mode requires a remote address"));
+                }
+                match passphrase.as_ref().map(Secret::expose) {
+                    // Restriction imposed by libsrt, references are checked once resolved
+                    Some(Ok(p)) if p.len() < 10 || p.len() > 79 => Err(eyre!(
+                        "SRT passphrase must be between 10 and 79 characters long"
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            Protocol::Hls { url } | Protocol::Http { url } => {
+                if url.starts_with("http://") || url.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err(eyre!("{} not recognized as a valid URL!", url))
+                }
+            }
+            Protocol::File { path } => {
+                if path.is_empty() {
+                    Err(eyre!("Source file path cannot be empty"))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SrtMode {
+    Caller,
+    Listener,
+}
+
+/// A rectangle in the comparison resolution, coordinates start at the top left corner.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mode: RegionMode,
+}
+
+impl Region {
+    fn is_valid(&self) -> Result<()> {
+        if self.width > 0 && self.height > 0 {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "Region at ({}, {}) must have a width and height greater than zero",
+                self.x,
+                self.y
+            ))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionMode {
+    /// Only the included regions are compared.
+    Include,
+    /// The region is ignored in the comparison, e.g. a burned-in clock.
+    Exclude,
+}
+
+/// Settings of the image comparison between the video frames and the slate.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DetectorSettings {
+    /// Maximum dissimilarity score (DSSIM multiplied by 1000) for a frame to match the slate.
+    pub threshold: Option<u32>,
+    /// Size the frames and the slate are scaled to before being compared, defaults to the size of
+    /// the slate image.
+    pub resolution: Option<Resolution>,
+    pub color_mode: Option<ColorMode>,
+    /// Enables the `black` mode.
+    pub black_frame: Option<BlackFrameDetection>,
+    /// Enables the `frozen` mode.
+    pub frozen_frame: Option<FrozenFrameDetection>,
+    /// Compares perceptual hashes of the frames and the slates first, so DSSIM only runs when the
+    /// hashes are inconclusive.
+    pub perceptual_hash: Option<PerceptualHash>,
+    /// Milliseconds between the frames analyzed, the frames in between are dropped before being
+    /// scaled. Every frame is analyzed by default.
+    pub frame_interval: Option<u32>,
+}
+
+impl DetectorSettings {
+    fn is_valid(&self) -> Result<()> {
+        if let Some(Resolution { width, height }) = self.resolution {
+            if width == 0 || height == 0 || width > 1920 || height > 1080 {
+                return Err(eyre!(
+                    "Detector resolution {}x{} is not within the valid range (1x1-1920x1080)",
+                    width,
+                    height
+                ));
+            }
+        }
+        if let Some(black) = self.black_frame.as_ref() {
+            if matches!(black.pixel_threshold, Some(value) if value > 100) {
+                return Err(eyre!("Black frame pixel threshold must be within 0-100"));
+            }
+            if matches!(black.ratio, Some(value) if value == 0 || value > 100) {
+                return Err(eyre!("Black frame ratio must be within 1-100"));
+            }
+        }
+        if let Some(frozen) = self.frozen_frame.as_ref() {
+            if matches!(frozen.max_difference, Some(value) if value > 100) {
+                return Err(eyre!(
+                    "Frozen frame maximum difference must be within 0-100"
+                ));
+            }
+        }
+        if let Some(hash) = self.perceptual_hash.as_ref() {
+            hash.is_valid()?;
+        }
+        if self.frame_interval == Some(0) {
+            return Err(eyre!("Detector frame interval must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+/// A frame is black when almost all of its pixels are dark.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BlackFrameDetection {
+    /// Maximum luminance (0-100) of a dark pixel, defaults to 10.
+    pub pixel_threshold: Option<u8>,
+    /// Minimum percentage of dark pixels in a black frame, defaults to 98.
+    pub ratio: Option<u8>,
+}
+
+/// The video is frozen when the frames barely change for long enough.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FrozenFrameDetection {
+    /// Seconds the frames must not change, defaults to 10.
+    pub duration: Option<u32>,
+    /// Maximum average difference of luminance (0-100) between frames considered the same,
+    /// defaults to 1.
+    pub max_difference: Option<u8>,
+}
+
+/// Hamming distances between the 64-bit hashes of a frame and a slate. Frames close enough
+/// match the slate and frames far enough don't, DSSIM decides for the frames in between.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PerceptualHash {
+    /// Defaults to `dhash`.
+    pub algorithm: Option<HashAlgorithm>,
+    /// Maximum distance (0-64) of a frame matching the slate without DSSIM, defaults to 4.
+    pub match_distance: Option<u32>,
+    /// Minimum distance (0-64) of a frame not matching the slate without DSSIM, defaults to 20.
+    pub mismatch_distance: Option<u32>,
+}
+
+impl PerceptualHash {
+    pub const DEFAULT_MATCH_DISTANCE: u32 = 4;
+    pub const DEFAULT_MISMATCH_DISTANCE: u32 = 20;
+
+    fn is_valid(&self) -> Result<()> {
+        let match_distance = self.match_distance.unwrap_or(Self::DEFAULT_MATCH_DISTANCE);
+        let mismatch_distance = self
+            .mismatch_distance
+            .unwrap_or(Self::DEFAULT_MISMATCH_DISTANCE);
+        if match_distance > 64 || mismatch_distance > 64 {
+            return Err(eyre!("Perceptual hash distances must be within 0-64"));
+        }
+        if match_distance >= mismatch_distance {
+            return Err(eyre!(
+                "Perceptual hash match distance must be lower than the mismatch distance"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// Average hash, each bit tells if a block is brighter than the mean.
+    Ahash,
+    /// Difference hash, each bit tells if a block is brighter than the next one.
+    Dhash,
+    /// Hash of the lowest frequencies of the discrete cosine transform, the most robust and the
+    /// slowest.
+    Phash,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Color,
+    Grayscale,
+}
+
+/// Measures the audio of the source to find silences. AAC and MPEG audio are supported.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AudioDetection {
+    /// Measure compared with the silence threshold, defaults to `rms`.
+    pub measure: Option<AudioMeasure>,
+    /// Level below which the audio is silent, in dBFS or LUFS, defaults to -50.
+    pub silence_threshold: Option<i32>,
+    /// Milliseconds the audio must stay below the threshold, defaults to 2000.
+    pub duration: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioMeasure {
+    /// Root mean square level, in dBFS.
+    Rms,
+    /// Momentary loudness of EBU R 128, in LUFS.
+    Lufs,
+}
+
+/// Reads the SCTE-35 cues carried in the MPEG-TS input, which enter the `cue_out` and `cue_in`
+/// modes.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Scte35Detection {
+    /// Milliseconds the cues and the slates found may disagree before it's counted, defaults to
+    /// 5000.
+    pub disagreement_timeout: Option<u32>,
+}
+
+/// Agreement required before the video is considered to have entered a new mode, so a single
+/// misdetected frame does not trigger a transition. When both are set, both must be satisfied.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Debounce {
+    /// Number of consecutive frames in the new mode.
+    pub frames: Option<u32>,
+    /// Milliseconds in the new mode.
+    pub duration: Option<u32>,
+}
+
+/// Named mode entered once its condition holds for long enough, e.g. an ad break when the video
+/// is in a slate and the audio is silent. The watcher stays in the mode of the last rule entered
+/// until another rule is entered, the first one in order when several hold.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ModeRule {
+    pub name: String,
+    pub condition: Condition,
+    /// Milliseconds the condition must hold before the mode is entered, defaults to 0.
+    pub duration: Option<u32>,
+}
+
+/// Combination of the modes detected, e.g. `{"all": [{"mode": "slate"}, {"mode": "silence"}]}`.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The video, or the audio, is in this mode.
+    Mode(VideoMode),
+    /// The video is in the slate with this label.
+    Slate(String),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn is_valid(&self) -> Result<()> {
+        match self {
+            Condition::Mode(_) | Condition::Slate(_) => Ok(()),
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                if conditions.is_empty() {
+                    return Err(eyre!("Mode rule conditions can't combine an empty list"));
+                }
+                conditions.iter().try_for_each(Condition::is_valid)
+            }
+            Condition::Not(condition) => condition.is_valid(),
+        }
+    }
+
+    /// Modes the condition depends on.
+    pub fn modes(&self) -> Vec<VideoMode> {
+        match self {
+            Condition::Mode(mode) => vec![*mode],
+            Condition::Slate(_) => vec![VideoMode::Slate],
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().flat_map(Condition::modes).collect()
+            }
+            Condition::Not(condition) => condition.modes(),
+        }
+    }
+}
+
+#[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Transition {
-    pub from: VideoMode,
-    pub to: VideoMode,
-    pub actions: Vec<Action>,
+    pub from: Mode,
+    pub to: Mode,
+    /// Restricts the transition to the slate with this label, the one entered or left.
+    pub slate_label: Option<String>,
+    /// Restricts the transition to when the audio is in this mode, `silence` or `audio`.
+    pub audio: Option<VideoMode>,
+    /// Minimum seconds between executions of each action, defaults to 5 seconds.
+    pub cooldown: Option<u32>,
+    /// Maximum number of executions of each action within an hour.
+    pub max_per_hour: Option<u32>,
+    pub actions: Vec<Step>,
+}
+
+/// Action of a transition, with the steps that run after it depending on its result. The steps
+/// of a branch run in order, and stop at the first one that fails.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    /// Name of the step, the response of its action is available to the templates of the steps
+    /// after it.
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub action: Action,
+    pub on_success: Option<Vec<Step>>,
+    pub on_failure: Option<Vec<Step>>,
+}
+
+impl Step {
+    /// The action of the step and of all the steps after it.
+    pub fn actions(&self) -> Vec<&Action> {
+        let mut actions = vec![&self.action];
+        for step in self
+            .on_success
+            .iter()
+            .chain(self.on_failure.iter())
+            .flatten()
+        {
+            actions.extend(step.actions());
+        }
+        actions
+    }
+
+    /// The action of the step and of all the steps after it.
+    pub fn actions_mut(&mut self) -> Vec<&mut Action> {
+        let mut actions = Vec::new();
+        self.collect_actions_mut(&mut actions);
+        actions
+    }
+
+    fn collect_actions_mut<'a>(&'a mut self, actions: &mut Vec<&'a mut Action>) {
+        actions.push(&mut self.action);
+        for step in self
+            .on_success
+            .iter_mut()
+            .chain(self.on_failure.iter_mut())
+            .flatten()
+        {
+            step.collect_actions_mut(actions);
+        }
+    }
+}
+
+impl From<Action> for Step {
+    fn from(action: Action) -> Self {
+        Self {
+            id: None,
+            action,
+            on_success: None,
+            on_failure: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
 pub enum VideoMode {
     Slate,
     Content,
+    /// No frames arrived for longer than the `no_signal_timeout` of the source.
+    NoSignal,
+    /// Mode of the watcher when it starts, before the first mode is detected.
+    Unknown,
+    Black,
+    Frozen,
+    /// The audio stayed below the silence threshold for long enough.
+    Silence,
+    /// The audio is above the silence threshold.
+    Audio,
+    /// A SCTE-35 cue of the input signalled the start of a break.
+    CueOut,
+    /// A SCTE-35 cue of the input signalled the end of a break.
+    CueIn,
+}
+
+impl VideoMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoMode::Slate => "slate",
+            VideoMode::Content => "content",
+            VideoMode::NoSignal => "no_signal",
+            VideoMode::Unknown => "unknown",
+            VideoMode::Black => "black",
+            VideoMode::Frozen => "frozen",
+            VideoMode::Silence => "silence",
+            VideoMode::Audio => "audio",
+            VideoMode::CueOut => "cue_out",
+            VideoMode::CueIn => "cue_in",
+        }
+    }
+
+    pub fn kind(&self) -> ModeKind {
+        match self {
+            VideoMode::Silence | VideoMode::Audio => ModeKind::Audio,
+            VideoMode::CueOut | VideoMode::CueIn => ModeKind::Cue,
+            _ => ModeKind::Video,
+        }
+    }
+}
+
+/// Modes of a kind change independently of the modes of the other kinds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ModeKind {
+    /// Modes found in the frames.
+    Video,
+    /// Modes found in the audio.
+    Audio,
+    /// Modes signalled by the SCTE-35 cues of the input.
+    Cue,
+    /// Modes of the mode rules.
+    Rule,
 }
 
+/// Mode a transition leaves or enters: a video mode, or the name of a mode rule.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Mode {
+    Video(VideoMode),
+    Rule(String),
+}
+
+impl Mode {
+    pub fn name(&self) -> &str {
+        match self {
+            Mode::Video(mode) => mode.name(),
+            Mode::Rule(name) => name,
+        }
+    }
+
+    pub fn kind(&self) -> ModeKind {
+        match self {
+            Mode::Video(mode) => mode.kind(),
+            Mode::Rule(_) => ModeKind::Rule,
+        }
+    }
+
+    pub fn video_mode(&self) -> Option<VideoMode> {
+        match self {
+            Mode::Video(mode) => Some(*mode),
+            Mode::Rule(_) => None,
+        }
+    }
+}
+
+impl From<VideoMode> for Mode {
+    fn from(mode: VideoMode) -> Self {
+        Mode::Video(mode)
+    }
+}
+
+impl PartialEq<VideoMode> for Mode {
+    fn eq(&self, other: &VideoMode) -> bool {
+        self.video_mode() == Some(*other)
+    }
+}
+
+// Actions are only built once, when the watcher is loaded
+#[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Action {
     HttpCall(HttpCall),
+    MediaLiveSchedule(MediaLiveSchedule),
+    Exec(Exec),
+    Scte35Cue(Scte35Cue),
 
     // #[cfg(test)]
     #[serde(skip_serializing, skip_deserializing)]
@@ -178,10 +929,94 @@ pub struct HttpCall {
     pub url: String,
     pub description: Option<String>,
     pub authorization: Option<HttpAuth>,
-    pub headers: Option<HashMap<String, String>>,
+    /// Values are secrets, since headers like `X-Api-Key` carry credentials.
+    pub headers: Option<HashMap<String, Secret>>,
     pub body: Option<String>,
+    /// Number of times the call is retried after the first attempt fails.
     pub retries: Option<u8>,
     pub timeout: Option<u32>,
+    /// Maximum seconds an execution can take, including retries, defaults to 60.
+    pub execution_timeout: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
+    /// Response status codes considered successful, defaults to any 2xx status.
+    pub expected_statuses: Option<Vec<u16>>,
+    pub expected_body: Option<BodyAssertion>,
+    /// Overrides the cooldown of the transition.
+    pub cooldown: Option<u32>,
+    /// Overrides the maximum executions per hour of the transition.
+    pub max_per_hour: Option<u32>,
+}
+
+/// Condition the response body must satisfy for the call to be successful.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BodyAssertion {
+    /// JSONPath of the value checked, e.g. `$.result.status`. The value must exist, and when not
+    /// set the whole body is checked.
+    pub json_path: Option<String>,
+    /// Regular expression the checked value must match.
+    pub regex: Option<String>,
+}
+
+impl BodyAssertion {
+    fn is_valid(&self) -> Result<()> {
+        if let Some(path) = self.json_path.as_ref() {
+            json_path::parse(path)?;
+        }
+        if let Some(pattern) = self.regex.as_ref() {
+            Regex::new(pattern)
+                .map_err(|err| eyre!("Invalid response assertion regex {}: {}", pattern, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// Delays between the retries of a failed call, and which failures are retried.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Milliseconds before the first retry, defaults to 500.
+    pub initial_delay: Option<u32>,
+    /// Factor the delay is multiplied by after each retry, defaults to 2.
+    pub multiplier: Option<u32>,
+    /// Maximum milliseconds between retries, defaults to 30 seconds.
+    pub max_delay: Option<u32>,
+    /// Maximum random milliseconds added to each delay.
+    pub jitter: Option<u32>,
+    /// Response status codes retried in addition to the `retryable_errors`.
+    pub retryable_statuses: Option<Vec<u16>>,
+    /// Kinds of failures retried, defaults to all of them.
+    pub retryable_errors: Option<Vec<RetryableError>>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryableError {
+    /// The connection to the server was not established in time.
+    ConnectTimeout,
+    /// The connection to the server failed or was interrupted.
+    ConnectionFailed,
+    /// The response was not received in time, or the program didn't exit in time.
+    Timeout,
+    /// Any 5xx response status.
+    ServerError,
+    /// A 429 response status, the delay requested in `Retry-After` is honored.
+    TooManyRequests,
+    /// The program exited with an unexpected code, or was killed.
+    ExitCode,
+}
+
+impl RetryableError {
+    pub fn all() -> Vec<Self> {
+        vec![
+            RetryableError::ConnectTimeout,
+            RetryableError::ConnectionFailed,
+            RetryableError::Timeout,
+            RetryableError::ServerError,
+            RetryableError::TooManyRequests,
+            RetryableError::ExitCode,
+        ]
+    }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
@@ -205,10 +1040,237 @@ impl ToString for HttpMethod {
     }
 }
 
+#[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum HttpAuth {
-    Basic { username: String, password: String },
+    Basic {
+        username: String,
+        password: Secret,
+    },
+    Bearer {
+        token: Secret,
+    },
+    /// Signs the request with the HMAC-SHA256 of `{timestamp}.{body}`, sending the hex encoded
+    /// signature and the Unix timestamp in seconds in the headers.
+    Hmac {
+        secret: Secret,
+        /// Defaults to `X-Signature`.
+        signature_header: Option<String>,
+        /// Defaults to `X-Timestamp`.
+        timestamp_header: Option<String>,
+    },
+    /// OAuth2 client credentials grant, the access token is cached until it expires.
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: Secret,
+        scope: Option<String>,
+    },
+}
+
+impl HttpAuth {
+    pub fn secret_mut(&mut self) -> &mut Secret {
+        match self {
+            HttpAuth::Basic { password, .. } => password,
+            HttpAuth::Bearer { token } => token,
+            HttpAuth::Hmac { secret, .. } => secret,
+            HttpAuth::OAuth2 { client_secret, .. } => client_secret,
+        }
+    }
+}
+
+const SECRET_ENV_PREFIX: &str = "env:";
+const REDACTED_SECRET: &str = "********";
+
+/// Credential of an action or of the source. Written inline, as `env:VAR` to read it from an environment variable
+/// of the worker, or as a reference to a key of a Kubernetes Secret.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Secret {
+    Reference { secret_ref: SecretRef },
+    Value(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SecretRef {
+    pub name: String,
+    pub key: String,
+}
+
+impl Secret {
+    /// Check if the plain value is written in the watcher.
+    pub fn is_inline(&self) -> bool {
+        match self {
+            Secret::Value(value) => !value.starts_with(SECRET_ENV_PREFIX),
+            Secret::Reference { .. } => false,
+        }
+    }
+
+    /// Reads the value of the secret. Kubernetes Secrets are read from the directory they are
+    /// mounted in, at `{secrets_path}/{name}/{key}`.
+    pub fn resolve(&self, secrets_path: &Path) -> Result<String> {
+        match self {
+            Secret::Value(value) if value.starts_with(SECRET_ENV_PREFIX) => {
+                let name = &value[SECRET_ENV_PREFIX.len()..];
+                std::env::var(name).map_err(|_| eyre!("Environment variable {} is not set", name))
+            }
+            Secret::Value(value) => Ok(value.clone()),
+            Secret::Reference { secret_ref } => {
+                let path = secrets_path.join(&secret_ref.name).join(&secret_ref.key);
+                let mut value = String::new();
+                File::open(&path)
+                    .and_then(|mut file| file.read_to_string(&mut value))
+                    .wrap_err_with(|| {
+                        format!(
+                            "Cannot read key {} of secret {}",
+                            secret_ref.key, secret_ref.name
+                        )
+                    })?;
+                Ok(value.trim_end_matches('\n').to_string())
+            }
+        }
+    }
+
+    /// The plain value, references are only available after being resolved.
+    pub fn expose(&self) -> Result<&str> {
+        match self {
+            Secret::Value(value) if self.is_inline() => Ok(value.as_str()),
+            _ => Err(eyre!("Secret was not resolved")),
+        }
+    }
+
+    /// Hides the plain value, references are kept.
+    pub fn redact(&mut self) {
+        if self.is_inline() {
+            *self = Secret::Value(REDACTED_SECRET.to_string());
+        }
+    }
+}
+
+/// Schedules a SCTE-35 message in an AWS Elemental MediaLive channel using the
+/// `BatchUpdateSchedule` API, the request is signed with the AWS credentials in the environment.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct MediaLiveSchedule {
+    pub description: Option<String>,
+    /// AWS region of the channel, e.g. `us-east-1`.
+    pub region: String,
+    pub channel_id: String,
+    pub splice: SpliceKind,
+    /// Template of the duration in seconds of the break started by a splice insert, e.g. `"120"`.
+    /// When not set the break lasts until a return to network is scheduled.
+    pub duration: Option<String>,
+    /// Overrides the MediaLive API endpoint of the region.
+    pub endpoint: Option<String>,
+    pub retries: Option<u8>,
+    pub timeout: Option<u32>,
+    /// Maximum seconds an execution can take, including retries, defaults to 60.
+    pub execution_timeout: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
+    /// Overrides the cooldown of the transition.
+    pub cooldown: Option<u32>,
+    /// Overrides the maximum executions per hour of the transition.
+    pub max_per_hour: Option<u32>,
+}
+
+/// Sends a SCTE-35 splice information section to a splicer or an encoder. Splice inserts are
+/// immediate, and time signals carry segmentation descriptors.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Scte35Cue {
+    pub description: Option<String>,
+    pub protocol: CueProtocol,
+    /// `host:port` the section is sent to.
+    pub address: String,
+    pub command: Scte35Command,
+    /// Splice event id of a splice insert, defaults to the event id of the detection.
+    pub event_id: Option<u32>,
+    /// Duration in seconds of the break started by a splice insert, the splicer returns to the
+    /// network when it ends.
+    pub duration: Option<u32>,
+    /// Set to `false` for a splice insert that returns to the network, defaults to `true`.
+    pub out_of_network: Option<bool>,
+    pub unique_program_id: Option<u16>,
+    pub segmentation_descriptors: Option<Vec<SegmentationDescriptor>>,
+    /// Number of times the section is sent again after the first attempt fails.
+    pub retries: Option<u8>,
+    /// Seconds to connect and send the section over TCP.
+    pub timeout: Option<u32>,
+    /// Maximum seconds an execution can take, including retries, defaults to 60.
+    pub execution_timeout: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
+    /// Overrides the cooldown of the transition.
+    pub cooldown: Option<u32>,
+    /// Overrides the maximum executions per hour of the transition.
+    pub max_per_hour: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CueProtocol {
+    Udp,
+    Tcp,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scte35Command {
+    SpliceInsert,
+    TimeSignal,
+}
+
+/// Segmentation descriptor of a SCTE-35 cue, restrictions on the delivery are not supported.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SegmentationDescriptor {
+    /// Defaults to the event id of the detection.
+    pub event_id: Option<u32>,
+    /// Segmentation type, e.g. 52 (0x34) for the start of a provider placement opportunity.
+    pub type_id: u8,
+    /// Duration of the segment in seconds.
+    pub duration: Option<u32>,
+    /// Type of the UPID, no UPID is sent by default.
+    pub upid_type: Option<u8>,
+    /// Hex encoded UPID.
+    pub upid: Option<String>,
+    pub segment_num: Option<u8>,
+    pub segments_expected: Option<u8>,
+}
+
+/// Runs a program in the worker, e.g. to toggle a local switcher or write a cue file. The
+/// arguments and the environment accept templates, and the context of the detection is also in
+/// the `HAWKEYE_*` environment variables.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Exec {
+    pub description: Option<String>,
+    pub program: String,
+    pub args: Option<Vec<String>>,
+    /// Values are secrets, since programs are usually given their credentials in the environment.
+    pub env: Option<HashMap<String, Secret>>,
+    /// Exit codes considered successful, defaults to 0.
+    pub expected_exit_codes: Option<Vec<i32>>,
+    /// Number of times the program is run again after the first run fails.
+    pub retries: Option<u8>,
+    /// Seconds the program can run before it is killed.
+    pub timeout: Option<u32>,
+    /// Maximum seconds an execution can take, including retries, defaults to 60.
+    pub execution_timeout: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
+    /// Overrides the cooldown of the transition.
+    pub cooldown: Option<u32>,
+    /// Overrides the maximum executions per hour of the transition.
+    pub max_per_hour: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpliceKind {
+    /// Starts an ad break.
+    SpliceInsert,
+    /// Ends the ad break started by the last splice insert.
+    ReturnToNetwork,
 }
 
 #[cfg(test)]
@@ -222,53 +1284,125 @@ mod tests {
         Watcher {
             id: Some("ee21fc9a-7225-450b-a2a7-2faf914e35b8".to_string()),
             description: Some("UEFA 2020 - Lyon vs. Bayern".to_string()),
-            slate_url: "file://./resources/slate_120px.jpg".to_string(),
+            slate_url: None,
+            slates: vec![
+                Slate {
+                    url: "file://./resources/slate_120px.jpg".to_string(),
+                    label: Some("ad-break".to_string()),
+                    threshold: None,
+                    regions: Some(vec![Region {
+                        x: 90,
+                        y: 0,
+                        width: 30,
+                        height: 10,
+                        mode: RegionMode::Exclude,
+                    }]),
+                },
+                Slate {
+                    url: "https://example.com/slates/technical-difficulties.jpg".to_string(),
+                    label: Some("technical-difficulties".to_string()),
+                    threshold: Some(500),
+                    regions: None,
+                },
+            ],
             status: Some(Status::Running),
             status_description: None,
             source: Source {
                 ingest_ip: None,
-                ingest_port: 5000,
+                ingest_port: Some(5000),
                 container: Container::MpegTs,
                 codec: Codec::H264,
-                transport: Protocol::Rtp
+                transport: Protocol::Rtp,
+                no_signal_timeout: Some(5000),
             },
+            detector: Some(DetectorSettings {
+                threshold: Some(900),
+                resolution: None,
+                color_mode: Some(ColorMode::Color),
+                black_frame: None,
+                frozen_frame: None,
+                perceptual_hash: None,
+                frame_interval: None,
+            }),
+            debounce: Some(Debounce {
+                frames: Some(3),
+                duration: None,
+            }),
+            audio: None,
+            scte35: None,
+            rules: None,
             transitions: vec![
                 Transition {
-                    from: VideoMode::Content,
-                    to: VideoMode::Slate,
+                    from: VideoMode::Content.into(),
+                    to: VideoMode::Slate.into(),
+                    slate_label: Some("ad-break".to_string()),
+                    audio: None,
+                    cooldown: Some(30),
+                    max_per_hour: Some(12),
                     actions: vec![
-                        Action::HttpCall( HttpCall {
+                        Step::from(Action::HttpCall( HttpCall {
                             description: Some("Trigger AdBreak using API".to_string()),
                             method: HttpMethod::POST,
                             url: "http://non-existent.cbs.com/v1/organization/cbsa/channel/slate4/ad-break".to_string(),
                             authorization: Some(HttpAuth::Basic {
                                 username: "dev_user".to_string(),
-                                password: "something".to_string()
+                                password: Secret::Value("something".to_string())
                             }),
-                            headers: Some([("Content-Type", "application/json")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<String, String>>()),
+                            headers: Some([("Content-Type", "application/json")].iter().map(|(k, v)| (k.to_string(), Secret::Value(v.to_string()))).collect::<HashMap<String, Secret>>()),
                             body: Some("{\"duration\":300}".to_string()),
                             retries: Some(3),
                             timeout: Some(10),
-                        })
+                            execution_timeout: Some(30),
+                            retry_policy: Some(RetryPolicy {
+                                initial_delay: Some(200),
+                                multiplier: Some(2),
+                                max_delay: Some(5000),
+                                jitter: Some(100),
+                                retryable_statuses: Some(vec![409]),
+                                retryable_errors: Some(vec![RetryableError::ConnectTimeout, RetryableError::ServerError, RetryableError::TooManyRequests]),
+                            }),
+                            expected_statuses: Some(vec![200, 201]),
+                            expected_body: Some(BodyAssertion {
+                                json_path: Some("$.status".to_string()),
+                                regex: Some("^(scheduled|started)$".to_string()),
+                            }),
+                            cooldown: None,
+                            max_per_hour: None,
+                        }))
                     ]
                 },
                 Transition {
-                    from: VideoMode::Slate,
-                    to: VideoMode::Content,
+                    from: VideoMode::Slate.into(),
+                    to: VideoMode::Content.into(),
+                    slate_label: None,
+                    audio: None,
+                    cooldown: None,
+                    max_per_hour: None,
                     actions: vec![
-                        Action::HttpCall( HttpCall {
+                        Step::from(Action::HttpCall( HttpCall {
                             description: Some("Use dump out of AdBreak API call".to_string()),
                             method: HttpMethod::DELETE,
                             url: "http://non-existent.cbs.com/v1/organization/cbsa/channel/slate4/ad-break".to_string(),
                             authorization: Some(HttpAuth::Basic {
                                 username: "dev_user".to_string(),
-                                password: "something".to_string()
+                                password: Secret::Reference {
+                                    secret_ref: SecretRef {
+                                        name: "ad-break-api".to_string(),
+                                        key: "password".to_string(),
+                                    }
+                                }
                             }),
                             headers: None,
                             body: None,
                             retries: None,
                             timeout: Some(10),
-                        })
+                            execution_timeout: None,
+                            retry_policy: None,
+                            expected_statuses: None,
+                            expected_body: None,
+                            cooldown: Some(60),
+                            max_per_hour: None,
+                        }))
                     ]
                 }
             ]
@@ -280,7 +1414,102 @@ mod tests {
         let mut w = get_watcher();
         assert!(w.is_valid().is_ok());
 
-        w.slate_url = String::from("something else");
+        w.slates[1].url = String::from("something else");
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_slate_regions_are_not_empty() {
+        let mut w = get_watcher();
+        w.slates[1].regions = Some(vec![Region {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 10,
+            mode: RegionMode::Include,
+        }]);
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_at_least_one_slate() {
+        let mut w = get_watcher();
+        w.slates = Vec::new();
+        assert!(w.is_valid().is_err());
+
+        // Watchers created with a single slate are still valid
+        w.slate_url = Some("file://./resources/slate_120px.jpg".to_string());
+        assert!(w.is_valid().is_ok());
+        assert_eq!(w.reference_slates().len(), 1);
+
+        // Black and frozen frames are detected without slates
+        w.slate_url = None;
+        w.detector = Some(DetectorSettings {
+            frozen_frame: Some(FrozenFrameDetection::default()),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_ok());
+    }
+
+    #[test]
+    fn check_black_frame_detection_is_in_range() {
+        let mut w = get_watcher();
+        w.detector = Some(DetectorSettings {
+            black_frame: Some(BlackFrameDetection {
+                pixel_threshold: Some(10),
+                ratio: Some(100),
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.detector = Some(DetectorSettings {
+            black_frame: Some(BlackFrameDetection {
+                pixel_threshold: None,
+                ratio: Some(0),
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_expected_body_assertion() {
+        fn assertion(w: &mut Watcher) -> &mut BodyAssertion {
+            w.transitions
+                .iter_mut()
+                .flat_map(|t| t.actions.iter_mut())
+                .flat_map(Step::actions_mut)
+                .find_map(|action| match action {
+                    Action::HttpCall(HttpCall {
+                        expected_body: Some(assertion),
+                        ..
+                    }) => Some(assertion),
+                    _ => None,
+                })
+                .unwrap()
+        }
+
+        let mut w = get_watcher();
+        assertion(&mut w).regex = Some("^(scheduled".to_string());
+        assert!(w.is_valid().is_err());
+
+        let mut w = get_watcher();
+        assertion(&mut w).json_path = Some("$..status".to_string());
+        assert!(w.is_valid().is_err());
+
+        // Steps after the action are checked too
+        let mut w = get_watcher();
+        let step = w.transitions[0].actions[0].clone();
+        w.transitions[0].actions[0] = Step {
+            on_failure: Some(vec![step]),
+            ..Step::from(Action::FakeAction(FakeAction {
+                called: Default::default(),
+                execute_returns: None,
+            }))
+        };
+        assert!(w.is_valid().is_ok());
+        assertion(&mut w).regex = Some("[".to_string());
         assert!(w.is_valid().is_err());
     }
 
@@ -289,8 +1518,503 @@ mod tests {
         let mut w = get_watcher();
         assert!(w.is_valid().is_ok());
 
-        w.source.ingest_port = 1000;
+        w.source.ingest_port = Some(1000);
         assert!(w.is_valid().is_err());
+
+        w.source.ingest_port = None;
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_no_signal_transitions() {
+        let mut w = get_watcher();
+        w.transitions[0].to = VideoMode::NoSignal.into();
+        assert!(w.is_valid().is_ok());
+
+        w.source.no_signal_timeout = None;
+        assert!(w.is_valid().is_err());
+
+        // The watcher only starts in the unknown mode
+        w.transitions[0].from = VideoMode::Unknown.into();
+        w.transitions[0].to = VideoMode::Content.into();
+        assert!(w.is_valid().is_ok());
+        w.transitions[0].to = VideoMode::Unknown.into();
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_audio_transitions() {
+        let mut w = get_watcher();
+        w.transitions[0].audio = Some(VideoMode::Silence);
+        assert!(w.is_valid().is_err());
+
+        w.audio = Some(AudioDetection::default());
+        assert!(w.is_valid().is_ok());
+
+        w.transitions[1].from = VideoMode::Audio.into();
+        w.transitions[1].to = VideoMode::Silence.into();
+        assert!(w.is_valid().is_ok());
+
+        // Audio and video modes change independently
+        w.transitions[1].to = VideoMode::Content.into();
+        assert!(w.is_valid().is_err());
+        w.transitions[1].to = VideoMode::Silence.into();
+        w.transitions[0].audio = Some(VideoMode::Slate);
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_scte35_transitions() {
+        let mut w = get_watcher();
+        w.transitions[0].from = VideoMode::CueIn.into();
+        w.transitions[0].to = VideoMode::CueOut.into();
+        assert!(w.is_valid().is_err());
+
+        w.scte35 = Some(Scte35Detection::default());
+        assert!(w.is_valid().is_ok());
+
+        // Only the transport stream carries the cues
+        w.transitions[0].from = VideoMode::Content.into();
+        w.transitions[0].to = VideoMode::Slate.into();
+        w.source.container = Container::RawVideo;
+        assert!(w.is_valid().is_err());
+        w.scte35 = None;
+        assert!(w.is_valid().is_ok());
+    }
+
+    #[test]
+    fn check_mode_rules() {
+        let mut w = get_watcher();
+        w.audio = Some(AudioDetection::default());
+        w.transitions[0].to = Mode::Rule("ad_break".to_string());
+        assert!(w.is_valid().is_err());
+
+        w.rules = Some(vec![ModeRule {
+            name: "ad_break".to_string(),
+            condition: Condition::All(vec![
+                Condition::Slate("ad-break".to_string()),
+                Condition::Mode(VideoMode::Silence),
+            ]),
+            duration: Some(2000),
+        }]);
+        // Rule modes only change to other rule modes
+        assert!(w.is_valid().is_err());
+        w.transitions[0].from = VideoMode::Unknown.into();
+        assert!(w.is_valid().is_ok());
+
+        let rules = w.rules.as_mut().unwrap();
+        rules[0].condition = Condition::Any(vec![]);
+        assert!(w.is_valid().is_err());
+        let rules = w.rules.as_mut().unwrap();
+        rules[0].condition = Condition::Mode(VideoMode::NoSignal);
+        w.source.no_signal_timeout = None;
+        assert!(w.is_valid().is_err());
+
+        let rules = w.rules.as_mut().unwrap();
+        rules[0].condition = Condition::Not(Box::new(Condition::Mode(VideoMode::Content)));
+        rules[0].name = "content".to_string();
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn deserialize_mode_rules() {
+        let rule: ModeRule = serde_json::from_str(
+            r#"{
+                "name": "ad_break",
+                "condition": {"all": [{"mode": "slate"}, {"not": {"mode": "audio"}}]},
+                "duration": 2000
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.condition,
+            Condition::All(vec![
+                Condition::Mode(VideoMode::Slate),
+                Condition::Not(Box::new(Condition::Mode(VideoMode::Audio))),
+            ])
+        );
+
+        let transition: Transition =
+            serde_json::from_str(r#"{"from": "no_signal", "to": "ad_break", "actions": []}"#)
+                .unwrap();
+        assert_eq!(transition.from, Mode::Video(VideoMode::NoSignal));
+        assert_eq!(transition.to, Mode::Rule("ad_break".to_string()));
+        assert_eq!(
+            serde_json::to_string(&transition.to).unwrap(),
+            r#""ad_break""#
+        );
+    }
+
+    #[test]
+    fn check_detector_resolution_is_in_range() {
+        let mut w = get_watcher();
+        w.detector = Some(DetectorSettings {
+            resolution: Some(Resolution {
+                width: 160,
+                height: 90,
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.detector = Some(DetectorSettings {
+            resolution: Some(Resolution {
+                width: 0,
+                height: 90,
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_perceptual_hash_and_frame_interval() {
+        let mut w = get_watcher();
+        w.detector = Some(DetectorSettings {
+            perceptual_hash: Some(PerceptualHash {
+                algorithm: Some(HashAlgorithm::Phash),
+                match_distance: Some(0),
+                mismatch_distance: Some(64),
+            }),
+            frame_interval: Some(500),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_ok());
+
+        // The match distance must stay below the default mismatch distance
+        w.detector = Some(DetectorSettings {
+            perceptual_hash: Some(PerceptualHash {
+                match_distance: Some(20),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_err());
+
+        w.detector = Some(DetectorSettings {
+            perceptual_hash: Some(PerceptualHash {
+                mismatch_distance: Some(65),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_err());
+
+        w.detector = Some(DetectorSettings {
+            frame_interval: Some(0),
+            ..Default::default()
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_container_and_codec_are_supported() {
+        let mut w = get_watcher();
+        w.source.codec = Codec::H265;
+        assert!(w.is_valid().is_ok());
+
+        w.source.container = Container::RawVideo;
+        assert!(w.is_valid().is_ok());
+
+        // Fragmented MP4 requires a pull based source
+        w.source.container = Container::Fmp4;
+        assert!(w.is_valid().is_err());
+        w.source.transport = Protocol::Http {
+            url: "http://packager.local/live/channel.mp4".to_string(),
+        };
+        assert!(w.is_valid().is_ok());
+        w.source.transport = Protocol::File {
+            path: "/media/channel.mp4".to_string(),
+        };
+        assert!(w.is_valid().is_ok());
+
+        // Raw video payload is only available over RTP
+        w.source.container = Container::RawVideo;
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Listener,
+            address: None,
+            passphrase: None,
+            latency: None,
+        };
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_srt_source() {
+        let mut w = get_watcher();
+        w.source.ingest_port = None;
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Caller,
+            address: None,
+            passphrase: None,
+            latency: Some(200),
+        };
+        // Caller mode must know where to connect to
+        assert!(w.is_valid().is_err());
+
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Caller,
+            address: Some("encoder.local:9000".to_string()),
+            passphrase: Some(Secret::Value("short".to_string())),
+            latency: Some(200),
+        };
+        assert!(w.is_valid().is_err());
+
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Caller,
+            address: Some("encoder.local:9000".to_string()),
+            passphrase: Some(Secret::Value("a-long-enough-secret".to_string())),
+            latency: Some(200),
+        };
+        assert!(w.is_valid().is_ok());
+
+        // Listener mode receives the feed in the ingest port
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Listener,
+            address: None,
+            passphrase: None,
+            latency: None,
+        };
+        assert!(w.is_valid().is_err());
+        w.source.ingest_port = Some(5000);
+        assert!(w.is_valid().is_ok());
+    }
+
+    #[test]
+    fn check_hls_source_does_not_require_port() {
+        let mut w = get_watcher();
+        w.source.ingest_port = None;
+        w.source.transport = Protocol::Hls {
+            url: "https://example.com/live/channel.m3u8".to_string(),
+        };
+        assert!(w.is_valid().is_ok());
+        assert_eq!(w.source.listen_port(), None);
+
+        w.source.transport = Protocol::Hls {
+            url: "channel.m3u8".to_string(),
+        };
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn deserialize_srt_transport() {
+        let transport: Protocol = serde_json::from_str(
+            r#"{"protocol": "srt", "mode": "caller", "address": "10.0.0.1:9000", "latency": 120}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            transport,
+            Protocol::Srt {
+                mode: SrtMode::Caller,
+                address: Some("10.0.0.1:9000".to_string()),
+                passphrase: None,
+                latency: Some(120),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_media_live_schedule_action() {
+        let action: Action = serde_json::from_str(
+            r#"{"type": "media_live_schedule", "region": "us-east-1", "channel_id": "1234567", "splice": "splice_insert", "duration": "{{ slate_label }}"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            action,
+            Action::MediaLiveSchedule(MediaLiveSchedule {
+                description: None,
+                region: "us-east-1".to_string(),
+                channel_id: "1234567".to_string(),
+                splice: SpliceKind::SpliceInsert,
+                duration: Some("{{ slate_label }}".to_string()),
+                endpoint: None,
+                retries: None,
+                timeout: None,
+                execution_timeout: None,
+                retry_policy: None,
+                cooldown: None,
+                max_per_hour: None,
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_scte35_cue_action() {
+        let action: Action = serde_json::from_str(
+            r#"{
+                "type": "scte35_cue",
+                "protocol": "udp",
+                "address": "10.0.0.5:5000",
+                "command": "time_signal",
+                "segmentation_descriptors": [
+                    {"type_id": 52, "duration": 300, "upid_type": 8, "upid": "000000002ca0a18a"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        match action {
+            Action::Scte35Cue(cue) => {
+                assert_eq!(cue.protocol, CueProtocol::Udp);
+                assert_eq!(cue.command, Scte35Command::TimeSignal);
+                assert_eq!(
+                    cue.segmentation_descriptors.unwrap(),
+                    vec![SegmentationDescriptor {
+                        event_id: None,
+                        type_id: 52,
+                        duration: Some(300),
+                        upid_type: Some(8),
+                        upid: Some("000000002ca0a18a".to_string()),
+                        segment_num: None,
+                        segments_expected: None,
+                    }]
+                );
+            }
+            action => panic!("Unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn deserialize_exec_action() {
+        let action: Action = serde_json::from_str(
+            r#"{"type": "exec", "program": "/usr/local/bin/switch", "args": ["--input", "{{ mode }}"], "timeout": 5, "retry_policy": {"retryable_errors": ["exit_code"]}}"#,
+        )
+        .unwrap();
+        match action {
+            Action::Exec(exec) => {
+                assert_eq!(exec.program, "/usr/local/bin/switch");
+                assert_eq!(
+                    exec.args,
+                    Some(vec!["--input".to_string(), "{{ mode }}".to_string()])
+                );
+                assert_eq!(exec.timeout, Some(5));
+                assert_eq!(
+                    exec.retry_policy.unwrap().retryable_errors,
+                    Some(vec![RetryableError::ExitCode])
+                );
+            }
+            action => panic!("Unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn deserialize_action_chain() {
+        let mut step: Step = serde_json::from_str(
+            r#"{
+                "id": "ad_server",
+                "type": "http_call",
+                "method": "POST",
+                "url": "http://ads.example.com/breaks",
+                "retries": 2,
+                "on_success": [
+                    {"type": "media_live_schedule", "region": "us-east-1", "channel_id": "1234567", "splice": "splice_insert"}
+                ],
+                "on_failure": [
+                    {"type": "http_call", "method": "POST", "url": "http://pager.example.com/alerts"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(step.id, Some("ad_server".to_string()));
+        match &step.action {
+            Action::HttpCall(call) => assert_eq!(call.retries, Some(2)),
+            action => panic!("Unexpected action {:?}", action),
+        }
+        assert!(matches!(
+            step.on_success.as_ref().unwrap()[0].action,
+            Action::MediaLiveSchedule(_)
+        ));
+        assert_eq!(step.on_failure.as_ref().unwrap().len(), 1);
+        assert_eq!(step.actions_mut().len(), 3);
+
+        let serialized = serde_json::to_string(&step).unwrap();
+        assert_eq!(serde_json::from_str::<Step>(&serialized).unwrap(), step);
+    }
+
+    #[test]
+    fn deserialize_http_auth() {
+        let auth: HttpAuth = serde_json::from_str(
+            r#"{"oauth2": {"token_url": "https://auth.example.com/token", "client_id": "hawkeye", "client_secret": "secret"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            auth,
+            HttpAuth::OAuth2 {
+                token_url: "https://auth.example.com/token".to_string(),
+                client_id: "hawkeye".to_string(),
+                client_secret: Secret::Value("secret".to_string()),
+                scope: None,
+            }
+        );
+
+        let auth: HttpAuth = serde_json::from_str(r#"{"hmac": {"secret": "secret"}}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&auth).unwrap(),
+            r#"{"hmac":{"secret":"secret"}}"#
+        );
+    }
+
+    #[test]
+    fn resolve_secrets() {
+        std::env::set_var("HAWKEYE_TEST_SECRET", "from-env");
+        let secrets_path = std::env::temp_dir().join("hawkeye-secrets");
+        std::fs::create_dir_all(secrets_path.join("ad-break-api")).unwrap();
+        std::fs::write(secrets_path.join("ad-break-api/password"), "from-file\n").unwrap();
+
+        let mut w = get_watcher();
+        assert!(w.secrets_mut()[2].expose().is_err());
+        w.resolve_secrets(&secrets_path).unwrap();
+        let values: Vec<String> = w
+            .secrets_mut()
+            .iter()
+            .map(|s| s.expose().unwrap().to_string())
+            .collect();
+        assert_eq!(values, vec!["something", "application/json", "from-file"]);
+
+        assert_eq!(
+            Secret::Value("env:HAWKEYE_TEST_SECRET".to_string())
+                .resolve(&secrets_path)
+                .unwrap(),
+            "from-env"
+        );
+        assert!(Secret::Value("env:HAWKEYE_MISSING_SECRET".to_string())
+            .resolve(&secrets_path)
+            .is_err());
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let mut w = get_watcher();
+        w.redact_secrets();
+        let secrets = w.secrets_mut();
+        assert_eq!(*secrets[0], Secret::Value("********".to_string()));
+        // Header values can carry credentials too
+        assert_eq!(*secrets[1], Secret::Value("********".to_string()));
+        // References are not sensitive
+        assert!(matches!(secrets[2], Secret::Reference { .. }));
+
+        // The passphrase of the source is redacted with the credentials of the actions
+        w.source.ingest_port = None;
+        w.source.transport = Protocol::Srt {
+            mode: SrtMode::Caller,
+            address: Some("encoder.local:9000".to_string()),
+            passphrase: Some(Secret::Value("a-long-enough-secret".to_string())),
+            latency: None,
+        };
+        w.redact_secrets();
+        assert_eq!(w.secrets_mut().len(), 4);
+        assert_eq!(
+            w.source.transport,
+            Protocol::Srt {
+                mode: SrtMode::Caller,
+                address: Some("encoder.local:9000".to_string()),
+                passphrase: Some(Secret::Value("********".to_string())),
+                latency: None,
+            }
+        );
+
+        let mut from_env = Secret::Value("env:API_TOKEN".to_string());
+        from_env.redact();
+        assert_eq!(from_env, Secret::Value("env:API_TOKEN".to_string()));
     }
 
     #[test]
@@ -0,0 +1,89 @@
+//! Frames per second the slate detector compares with a slate of 120px, by DSSIM alone and with
+//! the perceptual hashes that spare it from running DSSIM on most frames.
+//!
+//! `find_slate` times the comparison of decoded frames, and `detect` times what the worker does
+//! with every frame: decoding the PNG encoded by the pipeline and running the detectors.
+//!
+//! Run with `cargo bench -p hawkeye-worker`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use hawkeye_core::models::{DetectorSettings, HashAlgorithm, PerceptualHash, Slate};
+use hawkeye_worker::img_detector::{Detectors, Frame, SlateDetector};
+use image::ImageOutputFormat;
+use std::fs::File;
+
+const SLATE: &str = "../resources/slate_120px.jpg";
+const NON_SLATE: &str = "../resources/non-slate_120px.jpg";
+
+/// Perceptual hash settings of the detectors compared, `None` for DSSIM alone.
+const PREFILTERS: [(&str, Option<HashAlgorithm>); 4] = [
+    ("dssim", None),
+    ("ahash", Some(HashAlgorithm::Ahash)),
+    ("dhash", Some(HashAlgorithm::Dhash)),
+    ("phash", Some(HashAlgorithm::Phash)),
+];
+
+fn slates() -> Vec<(Slate, File)> {
+    let slate = Slate {
+        url: format!("file://{}", SLATE),
+        label: None,
+        threshold: None,
+        regions: None,
+    };
+    let file = File::open(SLATE).expect("Missing file in resources folder");
+    vec![(slate, file)]
+}
+
+fn settings(algorithm: Option<HashAlgorithm>) -> DetectorSettings {
+    DetectorSettings {
+        perceptual_hash: algorithm.map(|algorithm| PerceptualHash {
+            algorithm: Some(algorithm),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// The image encoded in PNG, like the frames given to the detectors by the pipeline.
+fn png(path: &str) -> Vec<u8> {
+    let img = image::open(path).expect("Missing file in resources folder");
+    let mut buffer = Vec::new();
+    img.write_to(&mut buffer, ImageOutputFormat::Png).unwrap();
+    buffer
+}
+
+fn find_slate(c: &mut Criterion) {
+    let frames = [
+        ("slate", Frame::decode(&png(SLATE)).unwrap()),
+        ("non-slate", Frame::decode(&png(NON_SLATE)).unwrap()),
+    ];
+    let mut group = c.benchmark_group("find_slate");
+    group.throughput(Throughput::Elements(1));
+    for (name, algorithm) in PREFILTERS.iter() {
+        let detector = SlateDetector::new(slates(), &settings(*algorithm)).unwrap();
+        for (kind, frame) in frames.iter() {
+            group.bench_function(format!("{}/{}", name, kind), |b| {
+                b.iter(|| detector.find_slate(black_box(frame)).is_some())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn detect(c: &mut Criterion) {
+    let frames = [("slate", png(SLATE)), ("non-slate", png(NON_SLATE))];
+    let mut group = c.benchmark_group("detect");
+    group.throughput(Throughput::Elements(1));
+    for (name, algorithm) in PREFILTERS.iter() {
+        let mut detectors = Detectors::new(slates(), &settings(*algorithm)).unwrap();
+        for (kind, frame) in frames.iter() {
+            group.bench_function(format!("{}/{}", name, kind), |b| {
+                b.iter(|| detectors.detect(black_box(frame)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, find_slate, detect);
+criterion_main!(benches);
@@ -1,178 +1,839 @@
+use crate::assertion::BodyCheck;
+use crate::auth;
+use crate::aws::Credentials;
+use crate::cue::CueAgreement;
+use crate::medialive;
 use crate::metrics::{
-    HTTP_CALL_DURATION, HTTP_CALL_ERROR_COUNTER, HTTP_CALL_RETRIED_COUNT,
-    HTTP_CALL_RETRIES_EXHAUSTED_COUNT, HTTP_CALL_SUCCESS_COUNTER,
+    ACTION_FAILED_COUNTER, ACTION_RATE_LIMITED_COUNTER, ACTION_TIMED_OUT_COUNTER,
+    EXEC_ATTEMPT_COUNTER, EXEC_DURATION, EXEC_ERROR_COUNTER, EXEC_RETRIED_COUNT,
+    EXEC_RETRIES_EXHAUSTED_COUNT, EXEC_RETRY_DELAY, EXEC_SUCCESS_COUNTER,
+    HTTP_CALL_ATTEMPT_COUNTER, HTTP_CALL_DURATION, HTTP_CALL_ERROR_COUNTER,
+    HTTP_CALL_RETRIED_COUNT, HTTP_CALL_RETRIES_EXHAUSTED_COUNT, HTTP_CALL_RETRY_DELAY,
+    HTTP_CALL_SUCCESS_COUNTER, MEDIALIVE_SCHEDULE_ATTEMPT_COUNTER,
+    MEDIALIVE_SCHEDULE_ERROR_COUNTER, MEDIALIVE_SCHEDULE_RETRIED_COUNT,
+    MEDIALIVE_SCHEDULE_RETRIES_EXHAUSTED_COUNT, MEDIALIVE_SCHEDULE_RETRY_DELAY,
+    MEDIALIVE_SCHEDULE_SUCCESS_COUNTER, SCTE35_CUE_ATTEMPT_COUNTER, SCTE35_CUE_ERROR_COUNTER,
+    SCTE35_CUE_RETRIED_COUNT, SCTE35_CUE_RETRIES_EXHAUSTED_COUNT, SCTE35_CUE_RETRY_DELAY,
+    SCTE35_CUE_SUCCESS_COUNTER,
 };
+use crate::queue::Queue;
+use crate::retry::{is_retryable_error, is_retryable_status, parse_retry_after, Backoff};
+use crate::rules::ModeRules;
+use crate::scte35;
+use crate::template::{self, Escape};
 use crate::video_stream::Event;
-use color_eyre::Result;
-use hawkeye_core::models::{self, Action, HttpAuth, HttpCall, VideoMode};
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use hawkeye_core::models::{
+    self, Action, Exec, HttpAuth, HttpCall, MediaLiveSchedule, Mode, ModeKind, RetryPolicy,
+    RetryableError, Scte35Cue, Secret, VideoMode,
+};
+use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
-use std::sync::mpsc::Receiver;
+use prometheus::{Histogram, IntCounter, IntCounterVec};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
 use std::time::Duration;
 
 #[cfg(test)]
 use sn_fake_clock::FakeClock as Instant;
 #[cfg(not(test))]
+use std::thread::sleep;
+#[cfg(not(test))]
 use std::time::Instant;
 
+/// Minimum time between executions of an action when no cooldown is configured.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5);
+
+const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum time an execution can take when no execution timeout is configured.
+const DEFAULT_EXECUTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time passes instantly in tests, so retry delays don't slow them down.
+#[cfg(test)]
+fn sleep(d: Duration) {
+    Instant::advance_time(d.as_millis() as u64);
+}
+
 /// Abstracts execution call for every action type.
 trait ActionExecution {
-    fn execute(&mut self) -> Result<()>;
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>>;
 }
 
 impl ActionExecution for Action {
-    fn execute(&mut self) -> Result<()> {
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>> {
         match self {
-            Action::HttpCall(a) => a.execute(),
-            Action::FakeAction(a) => a.execute(),
+            Action::HttpCall(a) => a.execute(context, deadline),
+            Action::MediaLiveSchedule(a) => a.execute(context, deadline),
+            Action::Exec(a) => a.execute(context, deadline),
+            Action::Scte35Cue(a) => a.execute(context, deadline),
+            Action::FakeAction(a) => a.execute().map(|_| None),
+        }
+    }
+}
+
+/// Response of an action, available to the templates of the steps after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    /// Status of the HTTP response, or exit code of the program.
+    pub status: u16,
+    /// Body of the HTTP response, or output of the program.
+    pub body: String,
+}
+
+/// Time by which an execution must be done. Executions check it before every attempt, and give
+/// up instead of retrying past it.
+#[derive(Clone, Copy)]
+struct Deadline(Instant);
+
+impl Deadline {
+    fn after(timeout: Duration) -> Self {
+        Deadline(Instant::now() + timeout)
+    }
+
+    /// Time left before the deadline, fails once it has passed.
+    fn remaining(&self) -> Result<Duration> {
+        let now = Instant::now();
+        if now < self.0 {
+            Ok(self.0.duration_since(now))
+        } else {
+            ACTION_TIMED_OUT_COUNTER.inc();
+            Err(eyre!("Action execution timed out"))
         }
     }
+
+    /// Check if the execution can wait this long and still have time left.
+    fn allows(&self, delay: Duration) -> bool {
+        Instant::now() + delay < self.0
+    }
+}
+
+/// Video mode the watcher is in, with the details of the transition into it that are made
+/// available to the action templates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionContext {
+    pub watcher_id: String,
+    /// Increases with every mode change.
+    pub event_id: u64,
+    pub mode: Mode,
+    /// Label of the slate the video is in.
+    pub slate_label: Option<String>,
+    /// Mode the video left, `Unknown` for the first mode after the watcher starts.
+    pub previous_mode: Mode,
+    pub previous_slate_label: Option<String>,
+    /// When the video entered the mode.
+    pub timestamp: DateTime<Utc>,
+    /// How long the video was in the previous mode.
+    pub previous_mode_duration: Duration,
+    /// Mode of the audio, `None` when the audio isn't monitored or wasn't measured yet.
+    pub audio: Option<VideoMode>,
+    /// Responses of the previous steps of the chain, by step id.
+    pub steps: HashMap<String, Response>,
 }
 
 /// Represents a sequence of video modes.
 #[derive(Clone, Eq, PartialEq)]
-pub struct Transition(VideoMode, VideoMode);
+pub struct Transition(Mode, Mode);
+
+impl Transition {
+    pub fn new(from: impl Into<Mode>, to: impl Into<Mode>) -> Self {
+        Self(from.into(), to.into())
+    }
+}
+
+/// Action with the steps that run after it, depending on its result.
+pub struct Step {
+    id: Option<String>,
+    action: Action,
+    /// Check of the response body of an HTTP call, compiled when the step is built.
+    body_check: Option<BodyCheck>,
+    timeout: Duration,
+    on_success: Vec<Step>,
+    on_failure: Vec<Step>,
+}
+
+impl Step {
+    /// Runs the action, then the branch selected by its result. The response of the action is
+    /// added to the context of the steps after it.
+    fn run(&mut self, context: &mut ActionContext) -> Result<()> {
+        let deadline = Deadline::after(self.timeout);
+        let result = match &mut self.action {
+            Action::HttpCall(call) => {
+                execute_call(call, self.body_check.as_ref(), context, deadline)
+            }
+            action => action.execute(context, deadline),
+        };
+        let result = result.map(|response| {
+            if let (Some(id), Some(response)) = (self.id.as_ref(), response) {
+                context.steps.insert(id.clone(), response);
+            }
+        });
+        let branch = match result.as_ref() {
+            Ok(_) => &mut self.on_success,
+            Err(err) => {
+                ACTION_FAILED_COUNTER.inc();
+                error!(
+                    "Error while processing action in mode {:?}: {:#}",
+                    context.mode, err
+                );
+                &mut self.on_failure
+            }
+        };
+        run_branch(branch, context);
+        result
+    }
+}
+
+/// Runs the steps in order, until one fails.
+fn run_branch(steps: &mut [Step], context: &mut ActionContext) {
+    for step in steps.iter_mut() {
+        if step.run(context).is_err() {
+            break;
+        }
+    }
+}
+
+impl From<models::Step> for Step {
+    fn from(step: models::Step) -> Self {
+        let timeout = execution_timeout(&step.action).map_or(DEFAULT_EXECUTION_TIMEOUT, |secs| {
+            Duration::from_secs(secs as u64)
+        });
+        let branch = |steps: Option<Vec<models::Step>>| {
+            steps.into_iter().flatten().map(Step::from).collect()
+        };
+        let body_check = match &step.action {
+            Action::HttpCall(HttpCall {
+                expected_body: Some(assertion),
+                ..
+            }) => Some(BodyCheck::new(assertion)),
+            _ => None,
+        };
+        Self {
+            id: step.id,
+            action: step.action,
+            body_check,
+            timeout,
+            on_success: branch(step.on_success),
+            on_failure: branch(step.on_failure),
+        }
+    }
+}
+
+impl From<Action> for Step {
+    fn from(action: Action) -> Self {
+        models::Step::from(action).into()
+    }
+}
 
 /// Manages the execution of an `Action` based on a flow of `VideoMode`s.
 ///
 /// The `ActionExecutor` abstracts the logic of execution that is inherent to all `Action` types.
 pub struct ActionExecutor {
     transition: Transition,
-    action: Action,
-    last_mode: Option<VideoMode>,
+    slate_label: Option<String>,
+    audio: Option<VideoMode>,
+    step: Step,
+    last_mode: Mode,
+    last_slate: Option<String>,
     last_call: Option<Instant>,
+    cooldown: Duration,
+    max_per_hour: Option<u32>,
+    /// Executions within the last hour, oldest first.
+    recent_calls: VecDeque<Instant>,
 }
 
 impl ActionExecutor {
     /// Creates a new `ActionExecutor` instance
-    pub fn new(transition: Transition, action: Action) -> Self {
+    pub fn new(transition: Transition, step: impl Into<Step>) -> Self {
         Self {
             transition,
-            action,
-            last_mode: None,
+            slate_label: None,
+            audio: None,
+            step: step.into(),
+            last_mode: VideoMode::Unknown.into(),
+            last_slate: None,
             last_call: None,
+            cooldown: DEFAULT_COOLDOWN,
+            max_per_hour: None,
+            recent_calls: VecDeque::new(),
         }
     }
 
+    /// Limits how often the action runs, the cooldown is the minimum time between successful
+    /// executions.
+    pub fn with_rate_limit(
+        mut self,
+        cooldown: Option<Duration>,
+        max_per_hour: Option<u32>,
+    ) -> Self {
+        self.cooldown = cooldown.unwrap_or(DEFAULT_COOLDOWN);
+        self.max_per_hour = max_per_hour;
+        self
+    }
+
+    /// Restricts the execution to transitions entering or leaving the slate with this label.
+    pub fn with_slate_label(mut self, slate_label: Option<String>) -> Self {
+        self.slate_label = slate_label;
+        self
+    }
+
+    /// Restricts the execution to when the audio is in this mode.
+    pub fn with_audio(mut self, audio: Option<VideoMode>) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Kind of the modes the transition follows.
+    pub fn kind(&self) -> ModeKind {
+        self.transition.1.kind()
+    }
+
     // Manage the execution of an action based on the provided video mode.
-    pub fn execute(&mut self, mode: VideoMode) {
-        if let Some(result) = self.call_action(mode) {
-            match result {
-                Ok(_) => self.last_call = Some(Instant::now()),
-                Err(err) => error!(
-                    "Error while processing action in mode {:?}: {:#}",
-                    mode, err
-                ),
-            }
+    pub fn execute(&mut self, context: &ActionContext) {
+        // The video, the audio and the mode rules change modes independently, only follow the
+        // ones of the transition
+        if context.mode.kind() != self.transition.1.kind() {
+            return;
         }
-        self.last_mode = Some(mode);
+        let (mode, slate) = (&context.mode, context.slate_label.as_deref());
+        // Failures are reported by the step
+        if let Some(Ok(_)) = self.call_action(mode, slate, context) {
+            self.last_call = Some(Instant::now());
+        }
+        self.last_mode = mode.clone();
+        self.last_slate = slate.map(String::from);
     }
 
     /// Executes the action if the video mode matches the transition and if the action is
     /// allowed to run.
-    fn call_action(&mut self, mode: VideoMode) -> Option<Result<()>> {
-        self.last_mode.and_then(|last_mode| {
-            if Transition(last_mode, mode) == self.transition && self.allowed_to_run() {
-                Some(self.action.execute())
-            } else {
-                None
-            }
-        })
+    fn call_action(
+        &mut self,
+        mode: &Mode,
+        slate: Option<&str>,
+        context: &ActionContext,
+    ) -> Option<Result<()>> {
+        if self.transition.0 == self.last_mode
+            && self.transition.1 == *mode
+            && self.slate_matches(mode, slate)
+            && (self.audio.is_none() || context.audio == self.audio)
+            && self.allowed_to_run()
+        {
+            self.recent_calls.push_back(Instant::now());
+            Some(self.step.run(&mut context.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Check if the slate entered, or left, has the label the executor is restricted to.
+    fn slate_matches(&self, mode: &Mode, slate: Option<&str>) -> bool {
+        let expected = match self.slate_label.as_ref() {
+            None => return true,
+            Some(expected) => expected.as_str(),
+        };
+        if *mode == VideoMode::Slate {
+            slate == Some(expected)
+        } else if self.last_mode == VideoMode::Slate {
+            self.last_slate.as_deref() == Some(expected)
+        } else {
+            false
+        }
     }
 
     /// Check if the action is allowed to run within the timeframe it was called.
     ///
     /// We need to limit the action frequency since the source of video mode does not guarantee the
-    /// ordering of events.
-    fn allowed_to_run(&self) -> bool {
-        match &self.last_call {
+    /// ordering of events, and a flapping video mode must not flood the backends.
+    fn allowed_to_run(&mut self) -> bool {
+        let cooled_down = match &self.last_call {
             None => true,
-            Some(last_call) => last_call.elapsed() > Duration::from_secs(5),
+            Some(last_call) => last_call.elapsed() >= self.cooldown,
+        };
+        if !cooled_down {
+            debug!("Action not executed, still in cooldown");
+            ACTION_RATE_LIMITED_COUNTER.inc();
+            return false;
+        }
+
+        if let Some(max_per_hour) = self.max_per_hour {
+            while self
+                .recent_calls
+                .front()
+                .map_or(false, |call| call.elapsed() >= ONE_HOUR)
+            {
+                self.recent_calls.pop_front();
+            }
+            if self.recent_calls.len() >= max_per_hour as usize {
+                warn!(
+                    "Action not executed, reached the limit of {} executions per hour",
+                    max_per_hour
+                );
+                ACTION_RATE_LIMITED_COUNTER.inc();
+                return false;
+            }
         }
+        true
     }
 }
 
 // TODO: Delete this type
-pub(crate) struct Executors(pub(crate) Vec<ActionExecutor>);
+pub struct Executors(pub Vec<ActionExecutor>);
 
 impl From<models::Transition> for Executors {
     fn from(transition: models::Transition) -> Self {
         let target_transition = Transition(transition.from, transition.to);
+        let (slate_label, audio) = (transition.slate_label, transition.audio);
+        let (transition_cooldown, transition_max_per_hour) =
+            (transition.cooldown, transition.max_per_hour);
         Self(
             transition
                 .actions
                 .into_iter()
-                .map(|action| ActionExecutor::new(target_transition.clone(), action))
+                .map(|step| {
+                    // Limits declared in the action take precedence over the transition ones, and
+                    // apply to the whole chain of steps
+                    let (cooldown, max_per_hour) = rate_limit(&step.action);
+                    let cooldown = cooldown
+                        .or(transition_cooldown)
+                        .map(|secs| Duration::from_secs(secs as u64));
+                    ActionExecutor::new(target_transition.clone(), step)
+                        .with_slate_label(slate_label.clone())
+                        .with_audio(audio)
+                        .with_rate_limit(cooldown, max_per_hour.or(transition_max_per_hour))
+                })
                 .collect(),
         )
     }
 }
 
+/// Cooldown (seconds) and maximum executions per hour declared in the action.
+fn rate_limit(action: &Action) -> (Option<u32>, Option<u32>) {
+    match action {
+        Action::HttpCall(call) => (call.cooldown, call.max_per_hour),
+        Action::MediaLiveSchedule(schedule) => (schedule.cooldown, schedule.max_per_hour),
+        Action::Exec(exec) => (exec.cooldown, exec.max_per_hour),
+        Action::Scte35Cue(cue) => (cue.cooldown, cue.max_per_hour),
+        Action::FakeAction(_) => (None, None),
+    }
+}
+
+/// Maximum seconds an execution of the action can take.
+fn execution_timeout(action: &Action) -> Option<u32> {
+    match action {
+        Action::HttpCall(call) => call.execution_timeout,
+        Action::MediaLiveSchedule(schedule) => schedule.execution_timeout,
+        Action::Exec(exec) => exec.execution_timeout,
+        Action::Scte35Cue(cue) => cue.execution_timeout,
+        Action::FakeAction(_) => None,
+    }
+}
+
+/// Contexts waiting for an action. A slow action only holds back its own contexts.
+struct Lane {
+    kind: ModeKind,
+    last: Option<(Mode, Option<String>)>,
+    contexts: Queue<ActionContext>,
+}
+
+impl Lane {
+    fn new(kind: ModeKind) -> Self {
+        Self {
+            kind,
+            last: None,
+            // Only mode changes are queued, and none of them can be dropped
+            contexts: Queue::unbounded(),
+        }
+    }
+
+    /// Queues the context when it changes the mode, or the slate, of the kind the action follows.
+    /// The repeats of every frame can't start a transition.
+    fn push(&mut self, context: &ActionContext) {
+        if context.mode.kind() != self.kind {
+            return;
+        }
+        let state = (context.mode.clone(), context.slate_label.clone());
+        if self.last.as_ref() == Some(&state) {
+            return;
+        }
+        self.last = Some(state);
+        self.contexts.push(context.clone());
+    }
+}
+
+/// Context of the modes of a kind.
+#[derive(Default)]
+struct Track {
+    context: Option<ActionContext>,
+    mode_since: Option<Instant>,
+}
+
+/// Dispatches the video modes to the actions. Each action runs in its own thread, so actions
+/// don't wait for each other.
 pub struct Runtime {
-    receiver: Receiver<Event>,
+    events: Queue<Event>,
     actions: Vec<ActionExecutor>,
+    watcher_id: String,
+    rules: ModeRules,
+    cue_check: Option<CueAgreement>,
+    tracks: HashMap<ModeKind, Track>,
+    next_event_id: u64,
 }
 
 impl Runtime {
-    pub fn new(receiver: Receiver<Event>, processors: Vec<ActionExecutor>) -> Self {
+    pub fn new(events: Queue<Event>, processors: Vec<ActionExecutor>) -> Self {
         Runtime {
-            receiver,
+            events,
             actions: processors,
+            watcher_id: String::new(),
+            rules: ModeRules::new(Vec::new()),
+            cue_check: None,
+            tracks: HashMap::new(),
+            // Starting from the current time keeps event ids increasing across restarts
+            next_event_id: Utc::now().timestamp() as u64,
         }
     }
 
+    /// Identifies the watcher in the action templates.
+    pub fn with_watcher_id(mut self, watcher_id: String) -> Self {
+        self.watcher_id = watcher_id;
+        self
+    }
+
+    /// Evaluates the mode rules on every mode detected, the rules entered are dispatched as
+    /// modes of their own.
+    pub fn with_rules(mut self, rules: ModeRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Cross-checks the SCTE-35 cues of the input against the slates found.
+    pub fn with_cue_check(mut self, cue_check: CueAgreement) -> Self {
+        self.cue_check = Some(cue_check);
+        self
+    }
+
     pub fn run_blocking(&mut self) -> Result<()> {
-        loop {
-            match self.receiver.recv()? {
+        let mut lanes = Vec::new();
+        let mut handles = Vec::new();
+        for mut executor in self.actions.drain(..) {
+            let lane = Lane::new(executor.kind());
+            let contexts = lane.contexts.clone();
+            handles.push(thread::spawn(move || {
+                while let Some(context) = contexts.pop() {
+                    executor.execute(&context);
+                }
+            }));
+            lanes.push(lane);
+        }
+
+        while let Some(event) = self.events.pop() {
+            match event {
                 Event::Terminate => break,
-                Event::Mode(mode) => {
-                    for p in self.actions.iter_mut() {
-                        p.execute(mode);
+                Event::Mode(mode, slate) => {
+                    if let Some(cue_check) = self.cue_check.as_mut() {
+                        cue_check.update(mode);
+                    }
+                    let rule = self.rules.update(mode, slate.as_deref());
+                    let mut contexts = vec![self.update_context(mode, slate)];
+                    if let Some(rule) = rule {
+                        contexts.push(self.update_context(Mode::Rule(rule), None));
+                    }
+                    for context in contexts.iter() {
+                        for lane in lanes.iter_mut() {
+                            lane.push(context);
+                        }
                     }
                 }
             }
         }
+
+        // Executions in progress finish before their deadline
+        for lane in lanes {
+            lane.contexts.close();
+        }
+        for handle in handles {
+            handle.join().map_err(|_| eyre!("Action thread panicked"))?;
+        }
         Ok(())
     }
+
+    /// Starts a new context when the video mode, or the slate, changes. The modes of the audio
+    /// and of the rules have their own contexts.
+    fn update_context(&mut self, mode: impl Into<Mode>, slate: Option<String>) -> ActionContext {
+        let mode = mode.into();
+        let audio = if mode.kind() == ModeKind::Audio {
+            mode.video_mode()
+        } else {
+            self.tracks
+                .get(&ModeKind::Audio)
+                .and_then(|track| track.context.as_ref())
+                .and_then(|context| context.mode.video_mode())
+        };
+        let track = self.tracks.entry(mode.kind()).or_default();
+        if let Some(context) = track.context.as_mut() {
+            if context.mode == mode && context.slate_label == slate {
+                context.audio = audio;
+                return context.clone();
+            }
+        }
+
+        let previous = track.context.take();
+        let context = ActionContext {
+            watcher_id: self.watcher_id.clone(),
+            event_id: self.next_event_id,
+            mode,
+            slate_label: slate,
+            previous_mode: previous
+                .as_ref()
+                .map_or(VideoMode::Unknown.into(), |c| c.mode.clone()),
+            previous_slate_label: previous.and_then(|c| c.slate_label),
+            timestamp: Utc::now(),
+            previous_mode_duration: track
+                .mode_since
+                .map_or(Duration::from_secs(0), |since| since.elapsed()),
+            audio,
+            steps: HashMap::new(),
+        };
+        self.next_event_id += 1;
+        track.mode_since = Some(Instant::now());
+        track.context = Some(context.clone());
+        context
+    }
 }
 
 impl ActionExecution for HttpCall {
-    fn execute(&mut self) -> Result<()> {
-        let mut tries = 0;
-        loop {
-            match try_call(&self) {
-                Ok(_) => break,
-                Err(err) => {
-                    HTTP_CALL_RETRIED_COUNT.inc();
-                    tries += 1;
-                    if tries >= self.retries.unwrap_or(0) {
-                        HTTP_CALL_RETRIES_EXHAUSTED_COUNT.inc();
-                        return Err(err);
-                    }
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>> {
+        let body_check = self.expected_body.as_ref().map(BodyCheck::new);
+        execute_call(self, body_check.as_ref(), context, deadline)
+    }
+}
+
+/// Calls the backend, the response body must pass the check compiled from the expected body.
+fn execute_call(
+    call: &HttpCall,
+    body_check: Option<&BodyCheck>,
+    context: &ActionContext,
+    deadline: Deadline,
+) -> Result<Option<Response>> {
+    let rendered = render_call(call, context)?;
+    let policy = call.retry_policy.clone().unwrap_or_default();
+    let retries = call.retries.unwrap_or(0) as u32;
+    with_retries(&policy, retries, deadline, &HTTP_CALL_METRICS, |timeout| {
+        try_call(&rendered, body_check, &policy, timeout)
+    })
+    .map(Some)
+}
+
+/// Metrics of the attempts of an action.
+struct AttemptMetrics {
+    name: &'static str,
+    attempts: &'static IntCounterVec,
+    retried: &'static IntCounter,
+    retry_delay: &'static Histogram,
+    retries_exhausted: &'static IntCounter,
+}
+
+lazy_static! {
+    static ref HTTP_CALL_METRICS: AttemptMetrics = AttemptMetrics {
+        name: "HTTP call",
+        attempts: &HTTP_CALL_ATTEMPT_COUNTER,
+        retried: &HTTP_CALL_RETRIED_COUNT,
+        retry_delay: &HTTP_CALL_RETRY_DELAY,
+        retries_exhausted: &HTTP_CALL_RETRIES_EXHAUSTED_COUNT,
+    };
+    static ref EXEC_METRICS: AttemptMetrics = AttemptMetrics {
+        name: "Exec",
+        attempts: &EXEC_ATTEMPT_COUNTER,
+        retried: &EXEC_RETRIED_COUNT,
+        retry_delay: &EXEC_RETRY_DELAY,
+        retries_exhausted: &EXEC_RETRIES_EXHAUSTED_COUNT,
+    };
+    static ref MEDIALIVE_SCHEDULE_METRICS: AttemptMetrics = AttemptMetrics {
+        name: "MediaLive schedule",
+        attempts: &MEDIALIVE_SCHEDULE_ATTEMPT_COUNTER,
+        retried: &MEDIALIVE_SCHEDULE_RETRIED_COUNT,
+        retry_delay: &MEDIALIVE_SCHEDULE_RETRY_DELAY,
+        retries_exhausted: &MEDIALIVE_SCHEDULE_RETRIES_EXHAUSTED_COUNT,
+    };
+    static ref SCTE35_CUE_METRICS: AttemptMetrics = AttemptMetrics {
+        name: "SCTE-35 cue",
+        attempts: &SCTE35_CUE_ATTEMPT_COUNTER,
+        retried: &SCTE35_CUE_RETRIED_COUNT,
+        retry_delay: &SCTE35_CUE_RETRY_DELAY,
+        retries_exhausted: &SCTE35_CUE_RETRIES_EXHAUSTED_COUNT,
+    };
+}
+
+/// Makes attempts until one succeeds, the failure is not retryable, the retries are exhausted or
+/// the next retry would start after the deadline. Each attempt is given the time left.
+fn with_retries<T>(
+    policy: &RetryPolicy,
+    retries: u32,
+    deadline: Deadline,
+    metrics: &AttemptMetrics,
+    mut try_attempt: impl FnMut(Duration) -> std::result::Result<T, AttemptFailure>,
+) -> Result<T> {
+    let mut backoff = Backoff::new(policy);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let attempt_label = attempt.to_string();
+        let remaining = deadline.remaining()?;
+        match try_attempt(remaining) {
+            Ok(value) => {
+                metrics
+                    .attempts
+                    .with_label_values(&[&attempt_label, "success"])
+                    .inc();
+                return Ok(value);
+            }
+            Err(failure) if failure.retryable && attempt <= retries => {
+                let delay = backoff.next_delay(failure.retry_after);
+                if !deadline.allows(delay) {
+                    metrics
+                        .attempts
+                        .with_label_values(&[&attempt_label, "failed"])
+                        .inc();
+                    ACTION_TIMED_OUT_COUNTER.inc();
+                    return Err(failure
+                        .error
+                        .wrap_err("Action execution timed out before the next retry"));
                 }
+                metrics
+                    .attempts
+                    .with_label_values(&[&attempt_label, "retried"])
+                    .inc();
+                metrics.retried.inc();
+                metrics.retry_delay.observe(delay.as_secs_f64());
+                warn!(
+                    "{} attempt {} failed, retrying in {}ms: {:#}",
+                    metrics.name,
+                    attempt,
+                    delay.as_millis(),
+                    failure.error
+                );
+                sleep(delay);
+            }
+            Err(failure) => {
+                metrics
+                    .attempts
+                    .with_label_values(&[&attempt_label, "failed"])
+                    .inc();
+                if failure.retryable {
+                    metrics.retries_exhausted.inc();
+                }
+                return Err(failure.error);
             }
         }
-        Ok(())
     }
 }
 
-fn try_call(call: &HttpCall) -> Result<()> {
+/// Evaluates the templates of the call with the context of the execution, values are
+/// percent-encoded in the URL and escaped in bodies with a JSON content type.
+fn render_call(call: &HttpCall, context: &ActionContext) -> Result<HttpCall> {
+    let mut rendered = call.clone();
+    rendered.url = template::render(&call.url, context, Escape::Url)?;
+    if let Some(headers) = call.headers.as_ref() {
+        let mut rendered_headers = HashMap::new();
+        for (k, v) in headers.iter() {
+            rendered_headers.insert(
+                k.clone(),
+                Secret::Value(template::render(v.expose()?, context, Escape::None)?),
+            );
+        }
+        rendered.headers = Some(rendered_headers);
+    }
+    if let Some(body) = call.body.as_ref() {
+        let escape = if has_json_content_type(call) {
+            Escape::Json
+        } else {
+            Escape::None
+        };
+        rendered.body = Some(template::render(body, context, escape)?);
+    }
+    Ok(rendered)
+}
+
+/// Whether the `Content-Type` header of the call is JSON, like `application/json`.
+fn has_json_content_type(call: &HttpCall) -> bool {
+    call.headers
+        .iter()
+        .flatten()
+        .any(|(name, value)| match value.expose() {
+            Ok(value) if name.eq_ignore_ascii_case("content-type") => {
+                let media_type = value.split(';').next().unwrap_or_default().trim();
+                media_type.eq_ignore_ascii_case("application/json")
+                    || media_type.to_ascii_lowercase().ends_with("+json")
+            }
+            _ => false,
+        })
+}
+
+/// Failed attempt of an action.
+struct AttemptFailure {
+    error: color_eyre::Report,
+    retryable: bool,
+    /// Delay requested by the server in the `Retry-After` header.
+    retry_after: Option<Duration>,
+}
+
+impl AttemptFailure {
+    fn new(error: color_eyre::Report, retryable: bool) -> Self {
+        Self {
+            error,
+            retryable,
+            retry_after: None,
+        }
+    }
+}
+
+/// Calls the backend once, the request is cancelled when it takes longer than the timeout.
+fn try_call(
+    call: &HttpCall,
+    body_check: Option<&BodyCheck>,
+    policy: &RetryPolicy,
+    timeout: Duration,
+) -> std::result::Result<Response, AttemptFailure> {
     let timer = HTTP_CALL_DURATION.start_timer();
     let method = call.method.to_string();
     let mut request = ureq::request(&method, call.url.as_str());
 
     request.timeout_connect(500);
 
-    if let Some(HttpAuth::Basic { username, password }) = &call.authorization {
-        request.auth(username, password);
+    match &call.authorization {
+        Some(HttpAuth::Basic { username, password }) => {
+            let password = password
+                .expose()
+                .map_err(|err| AttemptFailure::new(err, false))?;
+            request.auth(username, password);
+        }
+        Some(authorization) => {
+            let body = call.body.as_deref().unwrap_or("");
+            // Failing to authenticate, e.g. the token endpoint is unavailable, is worth a retry
+            let headers = auth::headers(authorization, body, Utc::now())
+                .map_err(|err| AttemptFailure::new(err, true))?;
+            for (k, v) in headers.iter() {
+                request.set(k, v);
+            }
+        }
+        None => {}
     }
 
-    if let Some(timeout) = &call.timeout {
-        request.timeout(Duration::from_secs(*timeout as u64));
-    }
+    request.timeout(match call.timeout {
+        Some(secs) => timeout.min(Duration::from_secs(secs as u64)),
+        None => timeout,
+    });
 
     if let Some(headers) = &call.headers {
         for (k, v) in headers.iter() {
-            request.set(k, v);
+            request.set(
+                k,
+                v.expose().map_err(|err| AttemptFailure::new(err, false))?,
+            );
         }
     }
 
@@ -180,20 +841,6 @@ fn try_call(call: &HttpCall) -> Result<()> {
         Some(data) => request.send_string(data),
         None => request.call(),
     };
-    if response.ok() {
-        HTTP_CALL_SUCCESS_COUNTER.inc();
-        debug!(
-            "Successfully called backend API {}",
-            response.into_string()?
-        );
-    } else {
-        HTTP_CALL_ERROR_COUNTER.inc();
-        warn!(
-            "Error while calling backend API ({}): {}",
-            response.status(),
-            response.into_string()?
-        );
-    }
 
     // Report how long it took to call the backend.
     // Keep it out of the log macro, so it will execute every time independent of log level
@@ -203,22 +850,390 @@ fn try_call(call: &HttpCall) -> Result<()> {
         Duration::from_secs_f64(seconds).as_millis()
     );
 
+    if let Some(error) = response.synthetic_error() {
+        HTTP_CALL_ERROR_COUNTER.inc();
+        let retryable = error_kind(error).map_or(false, |kind| is_retryable_error(policy, kind));
+        return Err(AttemptFailure::new(
+            eyre!("Error while calling backend API: {}", error),
+            retryable,
+        ));
+    }
+
+    let status = response.status();
+    let is_expected = match call.expected_statuses.as_ref() {
+        Some(statuses) => statuses.contains(&status),
+        None => response.ok(),
+    };
+    if !is_expected {
+        HTTP_CALL_ERROR_COUNTER.inc();
+        if let (401, Some(authorization)) = (status, call.authorization.as_ref()) {
+            auth::invalidate(authorization);
+        }
+        let retry_after = response
+            .header("retry-after")
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        return Err(AttemptFailure {
+            error: eyre!(
+                "Backend API responded with status {}: {}",
+                status,
+                response.into_string().unwrap_or_default()
+            ),
+            retryable: is_retryable_status(policy, status),
+            retry_after,
+        });
+    }
+
+    let body = response
+        .into_string()
+        .map_err(|err| AttemptFailure::new(err.into(), false))?;
+    if let Some(body_check) = body_check {
+        if let Err(err) = body_check.check(&body) {
+            HTTP_CALL_ERROR_COUNTER.inc();
+            return Err(AttemptFailure::new(
+                err.wrap_err("Unexpected response from backend API"),
+                false,
+            ));
+        }
+    }
+    HTTP_CALL_SUCCESS_COUNTER.inc();
+    debug!("Successfully called backend API {}", body);
+
+    Ok(Response { status, body })
+}
+
+/// Kind of the failure of a request that didn't get a response.
+fn error_kind(error: &ureq::Error) -> Option<RetryableError> {
+    match error {
+        ureq::Error::ConnectionFailed(message) if message.contains("timed out") => {
+            Some(RetryableError::ConnectTimeout)
+        }
+        ureq::Error::ConnectionFailed(_) => Some(RetryableError::ConnectionFailed),
+        ureq::Error::Io(err) if err.kind() == std::io::ErrorKind::TimedOut => {
+            Some(RetryableError::Timeout)
+        }
+        ureq::Error::Io(_) => Some(RetryableError::ConnectionFailed),
+        _ => None,
+    }
+}
+
+impl ActionExecution for MediaLiveSchedule {
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>> {
+        let credentials = Credentials::from_env()?;
+        let event_id = context.event_id as u32;
+        let duration = match self.duration.as_ref() {
+            Some(duration) => Some(render_seconds(duration, context)?),
+            None => None,
+        };
+        let body = medialive::schedule_request(self, duration, event_id, context.timestamp);
+        let policy = self.retry_policy.clone().unwrap_or_default();
+        let retries = self.retries.unwrap_or(0) as u32;
+        let action = &*self;
+        with_retries(
+            &policy,
+            retries,
+            deadline,
+            &MEDIALIVE_SCHEDULE_METRICS,
+            |timeout| try_schedule(action, &body, &credentials, &policy, timeout),
+        )?;
+        MEDIALIVE_SCHEDULE_SUCCESS_COUNTER.inc();
+        Ok(None)
+    }
+}
+
+/// Renders the template of a number of seconds.
+fn render_seconds(template: &str, context: &ActionContext) -> Result<u32> {
+    let rendered = template::render(template, context, Escape::None)?;
+    rendered.trim().parse().map_err(|_| {
+        eyre!(
+            "Expected a number of seconds, the template {:?} rendered {:?}",
+            template,
+            rendered
+        )
+    })
+}
+
+/// Updates the schedule of the channel once.
+fn try_schedule(
+    action: &MediaLiveSchedule,
+    body: &str,
+    credentials: &Credentials,
+    policy: &RetryPolicy,
+    timeout: Duration,
+) -> std::result::Result<(), AttemptFailure> {
+    let response = medialive::schedule(action, body, credentials, Utc::now(), timeout)
+        .map_err(|err| AttemptFailure::new(err, false))?;
+
+    if let Some(error) = response.synthetic_error() {
+        MEDIALIVE_SCHEDULE_ERROR_COUNTER.inc();
+        let retryable = error_kind(error).map_or(false, |kind| is_retryable_error(policy, kind));
+        return Err(AttemptFailure::new(
+            eyre!("Error while calling MediaLive: {}", error),
+            retryable,
+        ));
+    }
+    if !response.ok() {
+        MEDIALIVE_SCHEDULE_ERROR_COUNTER.inc();
+        let status = response.status();
+        let retry_after = response
+            .header("retry-after")
+            .and_then(|value| parse_retry_after(value, Utc::now()));
+        return Err(AttemptFailure {
+            error: eyre!(
+                "MediaLive schedule update failed ({}): {}",
+                status,
+                response.into_string().unwrap_or_default()
+            ),
+            retryable: is_retryable_status(policy, status),
+            retry_after,
+        });
+    }
+    info!(
+        "Scheduled {:?} in MediaLive channel {}",
+        action.splice, action.channel_id
+    );
     Ok(())
 }
 
+impl ActionExecution for Scte35Cue {
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>> {
+        let event_id = context.event_id as u32;
+        let policy = self.retry_policy.clone().unwrap_or_default();
+        let retries = self.retries.unwrap_or(0) as u32;
+        let cue = &*self;
+        with_retries(&policy, retries, deadline, &SCTE35_CUE_METRICS, |timeout| {
+            scte35::send(cue, event_id, timeout).map_err(|err| {
+                SCTE35_CUE_ERROR_COUNTER.inc();
+                let retryable =
+                    io_error_kind(&err).map_or(false, |kind| is_retryable_error(&policy, kind));
+                AttemptFailure::new(err, retryable)
+            })
+        })?;
+        SCTE35_CUE_SUCCESS_COUNTER.inc();
+        Ok(None)
+    }
+}
+
+/// Kind of the failure of a connection to a server, errors other than I/O errors are not
+/// retryable.
+fn io_error_kind(error: &color_eyre::Report) -> Option<RetryableError> {
+    error
+        .downcast_ref::<std::io::Error>()
+        .map(|err| match err.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+                RetryableError::Timeout
+            }
+            _ => RetryableError::ConnectionFailed,
+        })
+}
+
+/// Time between the checks of whether the program exited.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time the output is still read after the program exits, processes it leaves in the background
+/// can keep the output open much longer.
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+impl ActionExecution for Exec {
+    fn execute(&mut self, context: &ActionContext, deadline: Deadline) -> Result<Option<Response>> {
+        let exec = render_exec(self, context)?;
+        let policy = self.retry_policy.clone().unwrap_or_default();
+        let retries = self.retries.unwrap_or(0) as u32;
+        with_retries(&policy, retries, deadline, &EXEC_METRICS, |timeout| {
+            run_program(&exec, context, &policy, timeout)
+        })
+        .map(Some)
+    }
+}
+
+/// Evaluates the templates of the arguments and the environment with the context of the
+/// execution.
+fn render_exec(exec: &Exec, context: &ActionContext) -> Result<Exec> {
+    let mut rendered = exec.clone();
+    if let Some(args) = exec.args.as_ref() {
+        let mut rendered_args = Vec::new();
+        for arg in args.iter() {
+            rendered_args.push(template::render(arg, context, Escape::None)?);
+        }
+        rendered.args = Some(rendered_args);
+    }
+    if let Some(env) = exec.env.as_ref() {
+        let mut rendered_env = HashMap::new();
+        for (k, v) in env.iter() {
+            rendered_env.insert(
+                k.clone(),
+                Secret::Value(template::render(v.expose()?, context, Escape::None)?),
+            );
+        }
+        rendered.env = Some(rendered_env);
+    }
+    Ok(rendered)
+}
+
+/// Runs the program once, it is killed when it runs longer than the timeout.
+fn run_program(
+    exec: &Exec,
+    context: &ActionContext,
+    policy: &RetryPolicy,
+    timeout: Duration,
+) -> std::result::Result<Response, AttemptFailure> {
+    let timeout = match exec.timeout {
+        Some(secs) => timeout.min(Duration::from_secs(secs as u64)),
+        None => timeout,
+    };
+    let mut command = Command::new(&exec.program);
+    command
+        .args(exec.args.iter().flatten())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    for name in template::CONTEXT_VARIABLES.iter() {
+        let value =
+            template::value(name, context).map_err(|err| AttemptFailure::new(err, false))?;
+        command.env(format!("HAWKEYE_{}", name.to_uppercase()), value);
+    }
+    for (name, value) in exec.env.iter().flatten() {
+        command.env(
+            name,
+            value
+                .expose()
+                .map_err(|err| AttemptFailure::new(err, false))?,
+        );
+    }
+
+    let timer = EXEC_DURATION.start_timer();
+    let mut child = command.spawn().map_err(|err| {
+        EXEC_ERROR_COUNTER.inc();
+        AttemptFailure::new(eyre!("Cannot run {}: {}", exec.program, err), false)
+    })?;
+    // Read the output while the program runs, so it never blocks on a full pipe
+    let mut stdout = child.stdout.take().expect("Output of the program is piped");
+    let (sender, chunks) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        // Ends with the output, or with the first write once the output is no longer read
+        loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => {
+                    if sender.send(buffer[..read].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    });
+    let status = wait_timeout(&mut child, timeout);
+    let seconds = timer.stop_and_record();
+    info!(
+        "Program {} took: {}ms",
+        exec.program,
+        Duration::from_secs_f64(seconds).as_millis()
+    );
+
+    let status = match status {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            EXEC_ERROR_COUNTER.inc();
+            return Err(AttemptFailure::new(
+                eyre!(
+                    "Program {} did not exit in {}ms and was killed",
+                    exec.program,
+                    timeout.as_millis()
+                ),
+                is_retryable_error(policy, RetryableError::Timeout),
+            ));
+        }
+        Err(err) => {
+            EXEC_ERROR_COUNTER.inc();
+            return Err(AttemptFailure::new(err.into(), false));
+        }
+    };
+
+    // The exit code decides the result, the output is what was written until shortly after the
+    // exit even when processes left in the background keep it open
+    let mut output = Vec::new();
+    let exited = std::time::Instant::now();
+    while let Some(remaining) = OUTPUT_GRACE_PERIOD.checked_sub(exited.elapsed()) {
+        match chunks.recv_timeout(remaining) {
+            Ok(chunk) => output.extend(chunk),
+            Err(_) => break,
+        }
+    }
+    let body = String::from_utf8_lossy(&output).into_owned();
+    let is_expected = match (status.code(), exec.expected_exit_codes.as_ref()) {
+        (Some(code), Some(codes)) => codes.contains(&code),
+        (Some(code), None) => code == 0,
+        // Killed by a signal
+        (None, _) => false,
+    };
+    if !is_expected {
+        EXEC_ERROR_COUNTER.inc();
+        return Err(AttemptFailure::new(
+            eyre!(
+                "Program {} exited with {}: {}",
+                exec.program,
+                status,
+                body.trim()
+            ),
+            is_retryable_error(policy, RetryableError::ExitCode),
+        ));
+    }
+    EXEC_SUCCESS_COUNTER.inc();
+    debug!("Program {} ran successfully: {}", exec.program, body.trim());
+
+    Ok(Response {
+        status: status.code().unwrap_or_default() as u16,
+        body,
+    })
+}
+
+/// Waits for the program to exit, it is killed when it runs longer than the timeout.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    // Programs run in real time, also in the tests
+    let start = std::time::Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hawkeye_core::models::{FakeAction, HttpMethod};
+    use hawkeye_core::models::{
+        BodyAssertion, CueProtocol, FakeAction, HttpMethod, Scte35Command, Secret, SpliceKind,
+    };
     use mockito::{mock, server_url, Matcher};
-    use sn_fake_clock::FakeClock;
-    use std::collections::HashMap;
+    use std::net::{TcpListener, UdpSocket};
     use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::mpsc::channel;
     use std::sync::Arc;
 
-    fn sleep(d: Duration) {
-        FakeClock::advance_time(d.as_millis() as u64);
+    fn context(mode: impl Into<Mode>, slate: Option<&str>) -> ActionContext {
+        ActionContext {
+            watcher_id: "ee21fc9a".to_string(),
+            event_id: 1,
+            mode: mode.into(),
+            slate_label: slate.map(String::from),
+            previous_mode: VideoMode::Unknown.into(),
+            previous_slate_label: None,
+            timestamp: Utc::now(),
+            previous_mode_duration: Duration::from_secs(0),
+            audio: None,
+            steps: HashMap::new(),
+        }
+    }
+
+    fn deadline() -> Deadline {
+        Deadline::after(DEFAULT_EXECUTION_TIMEOUT)
     }
 
     #[test]
@@ -229,37 +1244,67 @@ mod tests {
             execute_returns: Some(Ok(())),
         };
         let mut executor = ActionExecutor::new(
-            Transition(VideoMode::Content, VideoMode::Slate),
+            Transition::new(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
+        executor.execute(&context(VideoMode::Content, None));
         // Didn't call since it was the first state found
         assert_eq!(called.load(Ordering::SeqCst), false);
 
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Slate, None));
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
     #[test]
-    fn executor_slate_action_cannot_be_called_twice_in_short_timeframe() {
-        let called = Arc::new(AtomicBool::new(false));
-        let fake_action = FakeAction {
-            called: called.clone(),
-            execute_returns: Some(Ok(())),
-        };
-        let mut executor = ActionExecutor::new(
-            Transition(VideoMode::Content, VideoMode::Slate),
+    fn executor_runs_transitions_from_unknown_and_to_no_signal() {
+        let started = Arc::new(AtomicBool::new(false));
+        let mut start_executor = ActionExecutor::new(
+            Transition::new(VideoMode::Unknown, VideoMode::Content),
+            Action::FakeAction(FakeAction {
+                called: started.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        );
+        let lost = Arc::new(AtomicBool::new(false));
+        let mut lost_executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::NoSignal),
+            Action::FakeAction(FakeAction {
+                called: lost.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        );
+
+        for executor in [&mut start_executor, &mut lost_executor].iter_mut() {
+            executor.execute(&context(VideoMode::Content, None));
+        }
+        // The first mode found leaves the unknown mode
+        assert!(started.load(Ordering::SeqCst));
+        assert!(!lost.load(Ordering::SeqCst));
+
+        lost_executor.execute(&context(VideoMode::NoSignal, None));
+        assert!(lost.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn executor_slate_action_cannot_be_called_twice_in_short_timeframe() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
         called.store(false, Ordering::SeqCst);
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
         assert_eq!(called.load(Ordering::SeqCst), false);
     }
 
@@ -271,11 +1316,11 @@ mod tests {
             execute_returns: Some(Ok(())),
         };
         let mut executor = ActionExecutor::new(
-            Transition(VideoMode::Content, VideoMode::Slate),
+            Transition::new(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
@@ -284,8 +1329,8 @@ mod tests {
         // Move time forward over the delay
         sleep(Duration::from_secs(11));
 
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
@@ -297,11 +1342,11 @@ mod tests {
             execute_returns: Some(Ok(())),
         };
         let mut executor = ActionExecutor::new(
-            Transition(VideoMode::Content, VideoMode::Slate),
+            Transition::new(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
@@ -310,10 +1355,165 @@ mod tests {
         // Move time forward over the delay
         sleep(Duration::from_secs(20));
 
-        executor.execute(VideoMode::Slate);
+        executor.execute(&context(VideoMode::Slate, None));
         assert_eq!(called.load(Ordering::SeqCst), false);
     }
 
+    #[test]
+    fn executor_honors_configured_cooldown() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action.clone()),
+        )
+        .with_rate_limit(Some(Duration::from_secs(60)), None);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        assert_eq!(called.load(Ordering::SeqCst), true);
+        called.store(false, Ordering::SeqCst);
+        executor.step.action = Action::FakeAction(fake_action.clone());
+
+        // The default cooldown has passed, but not the configured one
+        sleep(Duration::from_secs(30));
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        sleep(Duration::from_secs(30));
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[test]
+    fn executor_limits_executions_per_hour() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action.clone()),
+        )
+        .with_rate_limit(Some(Duration::from_secs(0)), Some(2));
+
+        let flap = |executor: &mut ActionExecutor| {
+            called.store(false, Ordering::SeqCst);
+            executor.step.action = Action::FakeAction(fake_action.clone());
+            executor.execute(&context(VideoMode::Content, None));
+            executor.execute(&context(VideoMode::Slate, None));
+            sleep(Duration::from_secs(10 * 60));
+            called.load(Ordering::SeqCst)
+        };
+        assert_eq!(flap(&mut executor), true);
+        assert_eq!(flap(&mut executor), true);
+        // Third time within the hour
+        assert_eq!(flap(&mut executor), false);
+        assert_eq!(flap(&mut executor), false);
+        assert_eq!(flap(&mut executor), false);
+        assert_eq!(flap(&mut executor), false);
+        // The first execution is more than one hour ago
+        assert_eq!(flap(&mut executor), true);
+    }
+
+    #[test]
+    fn executor_with_slate_label_only_called_for_that_slate() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition::new(VideoMode::Slate, VideoMode::Content),
+            Action::FakeAction(fake_action),
+        )
+        .with_slate_label(Some("ad-break".to_string()));
+
+        executor.execute(&context(VideoMode::Slate, Some("technical-difficulties")));
+        executor.execute(&context(VideoMode::Content, None));
+        // Left a slate with another label
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        executor.execute(&context(VideoMode::Slate, Some("ad-break")));
+        executor.execute(&context(VideoMode::Content, None));
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[test]
+    fn executor_follows_audio_modes_apart_from_video_modes() {
+        let silenced = Arc::new(AtomicBool::new(false));
+        let mut silence_executor = ActionExecutor::new(
+            Transition::new(VideoMode::Audio, VideoMode::Silence),
+            Action::FakeAction(FakeAction {
+                called: silenced.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        );
+        let slated = Arc::new(AtomicBool::new(false));
+        let mut slate_executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(FakeAction {
+                called: slated.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        )
+        .with_audio(Some(VideoMode::Silence));
+
+        let with_audio = |mode, audio| ActionContext {
+            audio: Some(audio),
+            ..context(mode, None)
+        };
+        for executor in [&mut silence_executor, &mut slate_executor].iter_mut() {
+            executor.execute(&with_audio(VideoMode::Audio, VideoMode::Audio));
+            executor.execute(&with_audio(VideoMode::Content, VideoMode::Audio));
+            // Video modes in between don't break the audio transition
+            executor.execute(&with_audio(VideoMode::Silence, VideoMode::Silence));
+        }
+        assert!(silenced.load(Ordering::SeqCst));
+        assert!(!slated.load(Ordering::SeqCst));
+
+        // Entered the slate while the audio is silent
+        slate_executor.execute(&with_audio(VideoMode::Slate, VideoMode::Silence));
+        assert!(slated.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn lane_queues_mode_changes_of_its_kind() {
+        let mut lane = Lane::new(ModeKind::Video);
+        let modes = [
+            (VideoMode::Content, None),
+            (VideoMode::Content, None),
+            (VideoMode::Silence, None),
+            (VideoMode::Slate, Some("ad-break")),
+            (VideoMode::Slate, Some("ad-break")),
+            (VideoMode::Slate, Some("technical")),
+            (VideoMode::Content, None),
+        ];
+        for (mode, slate) in modes.iter() {
+            lane.push(&context(*mode, *slate));
+        }
+        lane.contexts.close();
+
+        let mut queued = Vec::new();
+        while let Some(context) = lane.contexts.pop() {
+            queued.push((context.mode, context.slate_label));
+        }
+        assert_eq!(
+            queued,
+            vec![
+                (VideoMode::Content.into(), None),
+                (VideoMode::Slate.into(), Some("ad-break".to_string())),
+                (VideoMode::Slate.into(), Some("technical".to_string())),
+                (VideoMode::Content.into(), None),
+            ]
+        );
+    }
+
     #[test]
     fn runtime_calls_action_executor_with_video_mode() {
         let called = Arc::new(AtomicBool::new(false));
@@ -322,25 +1522,241 @@ mod tests {
             execute_returns: Some(Ok(())),
         };
         let mut executor = ActionExecutor::new(
-            Transition(VideoMode::Content, VideoMode::Slate),
+            Transition::new(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
         // Prepare executor to be ready in the next call with `VideoMode::Slate`
-        executor.execute(VideoMode::Content);
+        executor.execute(&context(VideoMode::Content, None));
         assert_eq!(called.load(Ordering::SeqCst), false);
 
-        let (s, r) = channel();
+        let events = Queue::bounded(10);
         // Pile up some events for the runtime to consume
-        s.send(Event::Mode(VideoMode::Slate)).unwrap();
-        s.send(Event::Terminate).unwrap();
+        events.push(Event::Mode(VideoMode::Slate, None));
+        events.push(Event::Terminate);
 
-        let mut runtime = Runtime::new(r, vec![executor]);
+        let mut runtime = Runtime::new(events, vec![executor]);
         runtime.run_blocking().expect("Should run successfully!");
 
         // Check the action was called
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
+    #[test]
+    fn runtime_tracks_mode_changes_in_context() {
+        let mut runtime =
+            Runtime::new(Queue::bounded(10), vec![]).with_watcher_id("ee21fc9a".to_string());
+
+        let first = runtime.update_context(VideoMode::Content, None);
+        assert_eq!(first.watcher_id, "ee21fc9a");
+        assert_eq!(first.previous_mode, VideoMode::Unknown);
+        sleep(Duration::from_secs(3));
+        // Same mode keeps the context
+        assert_eq!(
+            runtime.update_context(VideoMode::Content, None).event_id,
+            first.event_id
+        );
+
+        let slate = runtime.update_context(VideoMode::Slate, Some("ad-break".to_string()));
+        assert_eq!(slate.event_id, first.event_id + 1);
+        assert_eq!(slate.previous_mode, VideoMode::Content);
+        assert_eq!(slate.previous_mode_duration, Duration::from_secs(3));
+
+        sleep(Duration::from_secs(30));
+        let content = runtime.update_context(VideoMode::Content, None);
+        assert_eq!(content.event_id, first.event_id + 2);
+        assert_eq!(content.previous_slate_label, Some("ad-break".to_string()));
+        assert_eq!(content.previous_mode_duration, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn runtime_dispatches_mode_rules_entered() {
+        let called = Arc::new(AtomicBool::new(false));
+        let executor = ActionExecutor::new(
+            Transition::new(VideoMode::Unknown, Mode::Rule("outage".to_string())),
+            Action::FakeAction(FakeAction {
+                called: called.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        );
+        let rules = ModeRules::new(vec![models::ModeRule {
+            name: "outage".to_string(),
+            condition: models::Condition::Mode(VideoMode::Black),
+            duration: None,
+        }]);
+
+        let events = Queue::bounded(10);
+        events.push(Event::Mode(VideoMode::Content, None));
+        events.push(Event::Mode(VideoMode::Black, None));
+        events.push(Event::Terminate);
+        let mut runtime = Runtime::new(events, vec![executor]).with_rules(rules);
+        runtime.run_blocking().expect("Should run successfully!");
+        assert!(called.load(Ordering::SeqCst));
+
+        let context = runtime.update_context(Mode::Rule("program".to_string()), None);
+        assert_eq!(context.previous_mode, Mode::Rule("outage".to_string()));
+    }
+
+    #[test]
+    fn runtime_dispatches_cues() {
+        let called = Arc::new(AtomicBool::new(false));
+        let executor = ActionExecutor::new(
+            Transition::new(VideoMode::CueIn, VideoMode::CueOut),
+            Action::FakeAction(FakeAction {
+                called: called.clone(),
+                execute_returns: Some(Ok(())),
+            }),
+        );
+
+        let events = Queue::bounded(10);
+        events.push(Event::Mode(VideoMode::Content, None));
+        events.push(Event::Mode(VideoMode::CueIn, None));
+        events.push(Event::Mode(VideoMode::CueOut, None));
+        events.push(Event::Terminate);
+        let mut runtime =
+            Runtime::new(events, vec![executor]).with_cue_check(CueAgreement::new(None));
+        runtime.run_blocking().expect("Should run successfully!");
+        assert!(called.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn runtime_tracks_audio_apart_from_video() {
+        let mut runtime = Runtime::new(Queue::bounded(10), vec![]);
+
+        let content = runtime.update_context(VideoMode::Content, None);
+        assert_eq!(content.audio, None);
+        sleep(Duration::from_secs(5));
+        let audio = runtime.update_context(VideoMode::Audio, None);
+        assert_eq!(audio.previous_mode, VideoMode::Unknown);
+        assert_eq!(audio.audio, Some(VideoMode::Audio));
+        sleep(Duration::from_secs(2));
+        let silence = runtime.update_context(VideoMode::Silence, None);
+        assert_eq!(silence.previous_mode, VideoMode::Audio);
+        assert_eq!(silence.previous_mode_duration, Duration::from_secs(2));
+
+        // The video keeps its context, with the latest audio mode
+        let same = runtime.update_context(VideoMode::Content, None);
+        assert_eq!(same.event_id, content.event_id);
+        assert_eq!(same.audio, Some(VideoMode::Silence));
+        let slate = runtime.update_context(VideoMode::Slate, None);
+        assert_eq!(slate.previous_mode, VideoMode::Content);
+        assert_eq!(slate.previous_mode_duration, Duration::from_secs(7));
+        assert_eq!(slate.event_id, silence.event_id + 1);
+    }
+
+    #[test]
+    fn action_http_call_authenticates_with_oauth2() {
+        let token_endpoint = mock("POST", "/oauth2/action-token")
+            .with_body(r#"{"access_token": "action-token", "expires_in": 3600}"#)
+            .create();
+        let server = mock("GET", "/oauth2/protected")
+            .match_header("authorization", "Bearer action-token")
+            .create();
+
+        let mut action = http_call("/oauth2/protected", None, None);
+        action.authorization = Some(HttpAuth::OAuth2 {
+            token_url: format!("{}/oauth2/action-token", server_url()),
+            client_id: "hawkeye".to_string(),
+            client_secret: Secret::Value("secret".to_string()),
+            scope: None,
+        });
+
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Should execute successfully!");
+        token_endpoint.assert();
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_retries_with_new_token_after_401() {
+        let token_url = format!("{}/oauth2/rejected-token", server_url());
+        let rejected_token = mock("POST", "/oauth2/rejected-token")
+            .with_body(r#"{"access_token": "rejected", "expires_in": 3600}"#)
+            .expect(1)
+            .create();
+        let new_token = mock("POST", "/oauth2/rejected-token")
+            .with_body(r#"{"access_token": "accepted", "expires_in": 3600}"#)
+            .expect(1)
+            .create();
+        let rejected = mock("GET", "/oauth2/rejects-token")
+            .match_header("authorization", "Bearer rejected")
+            .with_status(401)
+            .expect(1)
+            .create();
+        let accepted = mock("GET", "/oauth2/rejects-token")
+            .match_header("authorization", "Bearer accepted")
+            .expect(1)
+            .create();
+
+        let policy = RetryPolicy {
+            retryable_statuses: Some(vec![401]),
+            ..RetryPolicy::default()
+        };
+        let mut action = http_call("/oauth2/rejects-token", Some(1), Some(policy));
+        action.authorization = Some(HttpAuth::OAuth2 {
+            token_url,
+            client_id: "hawkeye".to_string(),
+            client_secret: Secret::Value("secret".to_string()),
+            scope: None,
+        });
+
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Should execute successfully!");
+        rejected_token.assert();
+        new_token.assert();
+        rejected.assert();
+        accepted.assert();
+    }
+
+    #[test]
+    fn action_http_call_renders_templates() {
+        let server = mock("POST", "/templates/ee21fc9a")
+            .match_header("x-event-id", "1")
+            .match_body(r#"{"slate":"ad-break","mode":"slate"}"#)
+            .create();
+
+        let mut action = http_call("/templates/{{watcher_id}}", None, None);
+        action.method = HttpMethod::POST;
+        action.headers = Some(
+            [("x-event-id", "{{ event_id }}")]
+                .iter()
+                .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                .collect::<HashMap<String, Secret>>(),
+        );
+        action.body = Some(r#"{"slate":"{{slate_label}}","mode":"{{mode}}"}"#.to_string());
+
+        action
+            .execute(&context(VideoMode::Slate, Some("ad-break")), deadline())
+            .expect("Should execute successfully!");
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_escapes_template_values() {
+        let server = mock("POST", "/templates/ad%20break%2F%221%22")
+            .match_body(r#"{"slate":"ad break/\"1\""}"#)
+            .create();
+
+        let mut action = http_call("/templates/{{slate_label}}", None, None);
+        action.method = HttpMethod::POST;
+        action.headers = Some(
+            [("Content-Type", "application/json; charset=utf-8")]
+                .iter()
+                .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                .collect::<HashMap<String, Secret>>(),
+        );
+        action.body = Some(r#"{"slate":"{{slate_label}}"}"#.to_string());
+
+        action
+            .execute(
+                &context(VideoMode::Slate, Some("ad break/\"1\"")),
+                deadline(),
+            )
+            .expect("Should execute successfully!");
+        server.assert();
+    }
+
     #[test]
     fn action_http_call_performs_request() {
         let path = "/do-something";
@@ -359,28 +1775,602 @@ mod tests {
             description: None,
             authorization: Some(HttpAuth::Basic {
                 username: "user".to_string(),
-                password: "pass".to_string(),
+                password: Secret::Value("pass".to_string()),
             }),
             headers: Some(
                 [("content-type", "application/json")]
                     .iter()
-                    .map(|(k, v)| (k.to_string(), v.to_string()))
-                    .collect::<HashMap<String, String>>(),
+                    .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                    .collect::<HashMap<String, Secret>>(),
             ),
             body: Some(req_body.to_string()),
             retries: None,
             timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            expected_statuses: None,
+            expected_body: None,
+            cooldown: None,
+            max_per_hour: None,
         };
 
-        action.execute().expect("Should execute successfully!");
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Should execute successfully!");
         assert!(server.matched());
     }
 
+    fn http_call(path: &str, retries: Option<u8>, retry_policy: Option<RetryPolicy>) -> HttpCall {
+        HttpCall {
+            method: HttpMethod::GET,
+            url: format!("{}{}", server_url(), path),
+            description: None,
+            authorization: None,
+            headers: None,
+            body: None,
+            retries,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy,
+            expected_statuses: None,
+            expected_body: None,
+            cooldown: None,
+            max_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn action_http_call_retries_server_errors() {
+        let path = "/retries-server-errors";
+        let server = mock("GET", path).with_status(503).expect(3).create();
+
+        let mut action = http_call(path, Some(2), None);
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_does_not_retry_without_retries() {
+        let path = "/no-retries";
+        let server = mock("GET", path).with_status(500).expect(1).create();
+
+        let mut action = http_call(path, Some(0), None);
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_honors_retryable_statuses() {
+        let path = "/retryable-statuses";
+        let conflict = mock("GET", path).with_status(409).expect(2).create();
+
+        let policy = RetryPolicy {
+            retryable_statuses: Some(vec![409]),
+            retryable_errors: Some(vec![]),
+            ..RetryPolicy::default()
+        };
+        let mut action = http_call(path, Some(1), Some(policy.clone()));
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        conflict.assert();
+
+        // 5xx are not retried when not listed
+        let path = "/not-retryable-statuses";
+        let server_error = mock("GET", path).with_status(502).expect(1).create();
+        let mut action = http_call(path, Some(1), Some(policy));
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        server_error.assert();
+    }
+
+    #[test]
+    fn action_http_call_retries_too_many_requests_after_delay() {
+        let path = "/too-many-requests";
+        let server = mock("GET", path)
+            .with_status(429)
+            .with_header("retry-after", "7")
+            .expect(2)
+            .create();
+
+        let start = Instant::now();
+        let mut action = http_call(path, Some(1), None);
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        server.assert();
+        assert_eq!(start.elapsed(), Duration::from_secs(7));
+    }
+
+    #[test]
+    fn action_http_call_stops_retrying_at_deadline() {
+        let path = "/deadline";
+        let server = mock("GET", path).with_status(503).expect(2).create();
+        let policy = RetryPolicy {
+            initial_delay: Some(500),
+            ..RetryPolicy::default()
+        };
+
+        // The second retry would start after the deadline
+        let mut action = http_call(path, Some(5), Some(policy));
+        let deadline = Deadline::after(Duration::from_millis(1500));
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline)
+            .is_err());
+        server.assert();
+
+        let expired = Deadline::after(Duration::from_secs(0));
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), expired)
+            .is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_fails_on_unexpected_status() {
+        let path = "/unexpected-status";
+        let server = mock("GET", path).with_status(404).expect(1).create();
+
+        let mut action = http_call(path, Some(3), None);
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        server.assert();
+
+        let path = "/expected-status";
+        let server = mock("GET", path).with_status(404).create();
+        let mut action = http_call(path, None, None);
+        action.expected_statuses = Some(vec![200, 404]);
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("404 is expected");
+        server.assert();
+    }
+
+    #[test]
+    fn action_http_call_checks_response_body() {
+        let path = "/response-body";
+        let server = mock("GET", path)
+            .with_status(200)
+            .with_body(r#"{"result": {"status": "failed"}}"#)
+            .expect(2)
+            .create();
+
+        let mut action = http_call(path, Some(1), None);
+        action.expected_body = Some(BodyAssertion {
+            json_path: Some("$.result.status".to_string()),
+            regex: Some("^ok$".to_string()),
+        });
+        // Unexpected bodies are not retried
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+
+        action.expected_body = Some(BodyAssertion {
+            json_path: Some("$.result.status".to_string()),
+            regex: Some("^failed$".to_string()),
+        });
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Body is expected");
+        server.assert();
+    }
+
+    #[test]
+    fn executor_records_failed_execution() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Err(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition::new(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        );
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        assert!(called.load(Ordering::SeqCst));
+        assert!(executor.last_call.is_none());
+    }
+
+    fn media_live_schedule(channel_id: &str) -> MediaLiveSchedule {
+        std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+        std::env::set_var(
+            "AWS_SECRET_ACCESS_KEY",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        );
+        MediaLiveSchedule {
+            description: None,
+            region: "us-east-1".to_string(),
+            channel_id: channel_id.to_string(),
+            splice: SpliceKind::SpliceInsert,
+            duration: Some("{{ slate_label }}".to_string()),
+            endpoint: Some(server_url()),
+            retries: None,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            cooldown: None,
+            max_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn action_media_live_schedule_performs_signed_request() {
+        let path = "/prod/channels/1234567/schedule";
+        let server = mock("PUT", path)
+            .match_header("content-type", "application/json")
+            .match_header(
+                "authorization",
+                Matcher::Regex(
+                    r"^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/\d{8}/us-east-1/medialive/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=[0-9a-f]{64}$".to_string(),
+                ),
+            )
+            .match_header("x-amz-date", Matcher::Any)
+            .match_body(Matcher::Regex(
+                r#""Scte35SpliceInsertSettings":\{"Duration":2700000,"SpliceEventId":\d+\}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_body("{}")
+            .create();
+
+        let mut action = media_live_schedule("1234567");
+        action
+            .execute(&context(VideoMode::Slate, Some(" 30 ")), deadline())
+            .expect("Should execute successfully!");
+        assert!(server.matched());
+    }
+
+    #[test]
+    fn action_media_live_schedule_retries_server_errors() {
+        let path = "/prod/channels/2345678/schedule";
+        let server = mock("PUT", path).with_status(503).expect(3).create();
+
+        let mut action = media_live_schedule("2345678");
+        action.retries = Some(2);
+        assert!(action
+            .execute(&context(VideoMode::Slate, Some("30")), deadline())
+            .is_err());
+        server.assert();
+
+        let server = mock("PUT", path).with_status(400).expect(1).create();
+        assert!(action
+            .execute(&context(VideoMode::Slate, Some("30")), deadline())
+            .is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn action_media_live_schedule_retries_the_same_action() {
+        let path = "/prod/channels/4567890/schedule";
+        let context = context(VideoMode::Slate, Some("30"));
+        let server = mock("PUT", path)
+            .match_body(Matcher::Regex(format!(
+                r#""ActionName":"hawkeye-splice-insert-{}""#,
+                context.timestamp.timestamp_millis()
+            )))
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let mut action = media_live_schedule("4567890");
+        action.retries = Some(2);
+        assert!(action.execute(&context, deadline()).is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn action_media_live_schedule_requires_numeric_duration() {
+        let path = "/prod/channels/3456789/schedule";
+        let server = mock("PUT", path).expect(0).create();
+
+        let mut action = media_live_schedule("3456789");
+        let err = action
+            .execute(&context(VideoMode::Slate, Some("break")), deadline())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Expected a number of seconds, the template "{{ slate_label }}" rendered "break""#
+        );
+        server.assert();
+    }
+
+    fn exec(args: &[&str]) -> Exec {
+        Exec {
+            description: None,
+            program: "sh".to_string(),
+            args: Some(args.iter().map(|arg| arg.to_string()).collect()),
+            env: None,
+            expected_exit_codes: None,
+            retries: None,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            cooldown: None,
+            max_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn action_exec_runs_program_with_context() {
+        let mut action = exec(&[
+            "-c",
+            "echo \"$HAWKEYE_MODE $HAWKEYE_SLATE_LABEL $TARGET {{ event_id }}\"",
+        ]);
+        action.env = Some(
+            [("TARGET", "{{ watcher_id }}")]
+                .iter()
+                .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                .collect(),
+        );
+
+        let response = action
+            .execute(&context(VideoMode::Slate, Some("ad-break")), deadline())
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.body, "slate ad-break ee21fc9a 1\n");
+    }
+
+    #[test]
+    fn action_exec_retries_unexpected_exit_codes() {
+        let runs = std::env::temp_dir().join(format!("hawkeye-exec-runs-{}", std::process::id()));
+        let _ = std::fs::remove_file(&runs);
+        let mut action = exec(&["-c", "echo run >> \"$RUNS\"; exit 3"]);
+        action.env = Some(
+            [("RUNS", runs.to_str().unwrap())]
+                .iter()
+                .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                .collect(),
+        );
+        action.retries = Some(2);
+
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 3);
+
+        action.expected_exit_codes = Some(vec![0, 3]);
+        let response = action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status, 3);
+        std::fs::remove_file(&runs).unwrap();
+    }
+
+    #[test]
+    fn action_exec_kills_program_after_timeout() {
+        let mut action = exec(&["-c", "sleep 5"]);
+        action.timeout = Some(1);
+
+        let start = std::time::Instant::now();
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn action_exec_does_not_wait_for_background_processes() {
+        let mut action = exec(&["-c", "echo started; sleep 5 &"]);
+        action.timeout = Some(1);
+
+        let start = std::time::Instant::now();
+        let response = action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.body, "started\n");
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    fn scte35_cue(protocol: CueProtocol, address: String) -> Scte35Cue {
+        Scte35Cue {
+            description: None,
+            protocol,
+            address,
+            command: Scte35Command::SpliceInsert,
+            event_id: Some(1),
+            duration: None,
+            out_of_network: None,
+            unique_program_id: None,
+            segmentation_descriptors: None,
+            retries: None,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            cooldown: None,
+            max_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn action_scte35_cue_sends_udp_datagram() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut action = scte35_cue(CueProtocol::Udp, socket.local_addr().unwrap().to_string());
+
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Should execute successfully!");
+        let mut datagram = [0; 256];
+        let len = socket.recv(&mut datagram).unwrap();
+        let expected = scte35::section(&action, 0).unwrap().encode();
+        assert_eq!(&datagram[..len], expected.as_slice());
+    }
+
+    #[test]
+    fn action_scte35_cue_sends_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut action = scte35_cue(CueProtocol::Tcp, listener.local_addr().unwrap().to_string());
+        let receiver = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut section = Vec::new();
+            stream.read_to_end(&mut section).unwrap();
+            section
+        });
+
+        action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .expect("Should execute successfully!");
+        let expected = scte35::section(&action, 0).unwrap().encode();
+        assert_eq!(receiver.join().unwrap(), expected);
+    }
+
+    #[test]
+    fn action_scte35_cue_retries_with_backoff() {
+        // Nothing listens on the port once the listener is dropped
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let mut action = scte35_cue(CueProtocol::Tcp, address);
+        action.retries = Some(2);
+        action.retry_policy = Some(RetryPolicy {
+            initial_delay: Some(100),
+            multiplier: Some(2),
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+        let err = action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .unwrap_err();
+        assert!(err.downcast_ref::<std::io::Error>().is_some());
+        // Waited 100ms and then 200ms between the attempts
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+
+        // Connection failures are not retried when the policy excludes them
+        action.retry_policy = Some(RetryPolicy {
+            initial_delay: Some(1000),
+            retryable_errors: Some(vec![RetryableError::Timeout]),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!(action
+            .execute(&context(VideoMode::Slate, None), deadline())
+            .is_err());
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+    }
+
+    fn step(id: Option<&str>, call: HttpCall) -> models::Step {
+        models::Step {
+            id: id.map(String::from),
+            action: Action::HttpCall(call),
+            on_success: None,
+            on_failure: None,
+        }
+    }
+
+    #[test]
+    fn executor_runs_success_branch_with_previous_responses() {
+        let ad_server = mock("POST", "/chain/ad-server")
+            .with_body(r#"{"break": {"id": "b-42"}}"#)
+            .create();
+        let schedule = mock("PUT", "/chain/schedule/b-42")
+            .match_body("200")
+            .create();
+        let page = mock("POST", "/chain/page").expect(0).create();
+
+        let mut ad_server_call = http_call("/chain/ad-server", None, None);
+        ad_server_call.method = HttpMethod::POST;
+        let mut schedule_call = http_call(
+            "/chain/schedule/{{ steps.ad_server.body.break.id }}",
+            None,
+            None,
+        );
+        schedule_call.method = HttpMethod::PUT;
+        schedule_call.body = Some("{{ steps.ad_server.status }}".to_string());
+        let mut page_call = http_call("/chain/page", None, None);
+        page_call.method = HttpMethod::POST;
+        let mut chain = step(Some("ad_server"), ad_server_call);
+        chain.on_success = Some(vec![step(None, schedule_call)]);
+        chain.on_failure = Some(vec![step(None, page_call)]);
+
+        let mut executor =
+            ActionExecutor::new(Transition::new(VideoMode::Content, VideoMode::Slate), chain);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        ad_server.assert();
+        schedule.assert();
+        page.assert();
+    }
+
+    #[test]
+    fn executor_checks_response_body_of_steps() {
+        let ad_server = mock("POST", "/chain-body/ad-server")
+            .with_body(r#"{"result": "rejected"}"#)
+            .create();
+        let page = mock("POST", "/chain-body/page").create();
+
+        let mut ad_server_call = http_call("/chain-body/ad-server", None, None);
+        ad_server_call.method = HttpMethod::POST;
+        ad_server_call.expected_body = Some(BodyAssertion {
+            json_path: Some("$.result".to_string()),
+            regex: Some("^accepted$".to_string()),
+        });
+        let mut page_call = http_call("/chain-body/page", None, None);
+        page_call.method = HttpMethod::POST;
+        let mut chain = step(None, ad_server_call);
+        chain.on_failure = Some(vec![step(None, page_call)]);
+
+        let mut executor =
+            ActionExecutor::new(Transition::new(VideoMode::Content, VideoMode::Slate), chain);
+        assert!(executor.step.body_check.is_some());
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        ad_server.assert();
+        page.assert();
+        assert!(executor.last_call.is_none());
+    }
+
+    #[test]
+    fn executor_runs_failure_branch_in_order() {
+        let ad_server = mock("POST", "/chain-failure/ad-server")
+            .with_status(500)
+            .create();
+        let page = mock("POST", "/chain-failure/page")
+            .with_status(500)
+            .create();
+        let log = mock("POST", "/chain-failure/log").expect(0).create();
+
+        let mut ad_server_call = http_call("/chain-failure/ad-server", None, None);
+        ad_server_call.method = HttpMethod::POST;
+        let mut page_call = http_call("/chain-failure/page", None, None);
+        page_call.method = HttpMethod::POST;
+        let mut log_call = http_call("/chain-failure/log", None, None);
+        log_call.method = HttpMethod::POST;
+        let mut chain = step(None, ad_server_call);
+        // The branch stops at the first failure
+        chain.on_failure = Some(vec![step(None, page_call), step(None, log_call)]);
+
+        let mut executor =
+            ActionExecutor::new(Transition::new(VideoMode::Content, VideoMode::Slate), chain);
+        executor.execute(&context(VideoMode::Content, None));
+        executor.execute(&context(VideoMode::Slate, None));
+        ad_server.assert();
+        page.assert();
+        log.assert();
+        assert!(executor.last_call.is_none());
+    }
+
     #[test]
     fn build_executor_from_models() {
         let transition = models::Transition {
-            from: models::VideoMode::Content,
-            to: models::VideoMode::Slate,
+            from: models::VideoMode::Content.into(),
+            to: models::VideoMode::Slate.into(),
+            slate_label: None,
+            audio: None,
+            cooldown: Some(60),
+            max_per_hour: Some(10),
             actions: vec![models::Action::HttpCall(HttpCall {
                 description: Some("Trigger AdBreak using API".to_string()),
                 method: HttpMethod::POST,
@@ -388,18 +2378,25 @@ mod tests {
                     .to_string(),
                 authorization: Some(HttpAuth::Basic {
                     username: "dev_user".to_string(),
-                    password: "something".to_string(),
+                    password: Secret::Value("something".to_string()),
                 }),
                 headers: Some(
                     [("content-type", "application/json")]
                         .iter()
-                        .map(|(k, v)| (k.to_string(), v.to_string()))
-                        .collect::<HashMap<String, String>>(),
+                        .map(|(k, v)| (k.to_string(), Secret::Value(v.to_string())))
+                        .collect::<HashMap<String, Secret>>(),
                 ),
                 body: Some("{\"duration\":320}".to_string()),
                 retries: Some(3),
                 timeout: Some(10),
-            })],
+                execution_timeout: None,
+                retry_policy: None,
+                expected_statuses: None,
+                expected_body: None,
+                cooldown: None,
+                max_per_hour: None,
+            })
+            .into()],
         };
 
         let _executors: Executors = transition.into();
@@ -0,0 +1,127 @@
+use color_eyre::{eyre::eyre, Result};
+use hawkeye_core::json_path::{self, Selector};
+use hawkeye_core::models::BodyAssertion;
+use regex::Regex;
+use serde_json::Value;
+
+/// Check of the response body, with the regex of the assertion compiled once.
+#[derive(Clone, Debug)]
+pub struct BodyCheck {
+    json_path: Option<String>,
+    regex: Option<(String, std::result::Result<Regex, regex::Error>)>,
+}
+
+impl BodyCheck {
+    /// Compiles the regex of the assertion, which watchers validate before the executors are
+    /// built. An invalid regex fails every check.
+    pub fn new(assertion: &BodyAssertion) -> Self {
+        Self {
+            json_path: assertion.json_path.clone(),
+            regex: assertion
+                .regex
+                .as_ref()
+                .map(|pattern| (pattern.clone(), Regex::new(pattern))),
+        }
+    }
+
+    /// Check the response body satisfies the assertion.
+    pub fn check(&self, body: &str) -> Result<()> {
+        let checked = match self.json_path.as_ref() {
+            Some(path) => json_value(body, path)?,
+            None => body.to_string(),
+        };
+
+        if let Some((pattern, regex)) = self.regex.as_ref() {
+            let regex = regex
+                .as_ref()
+                .map_err(|err| eyre!("Invalid response assertion regex {}: {}", pattern, err))?;
+            if !regex.is_match(&checked) {
+                return Err(eyre!(
+                    "Response value {:?} does not match {}",
+                    checked,
+                    pattern
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value at the JSONPath of the JSON body, strings are returned without quotes.
+pub fn json_value(body: &str, path: &str) -> Result<String> {
+    let document: Value = serde_json::from_str(body)
+        .map_err(|err| eyre!("Response body is not valid JSON: {}", err))?;
+    match select(&document, path)? {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(Value::Null) | None => Err(eyre!("Response body has no value at {}", path)),
+        Some(value) => Ok(value.to_string()),
+    }
+}
+
+/// Selects the value at the JSONPath.
+fn select<'a>(document: &'a Value, path: &str) -> Result<Option<&'a Value>> {
+    let mut current = document;
+    for selector in json_path::parse(path)? {
+        let next = match selector {
+            Selector::Key(key) => current.get(&key),
+            Selector::Index(index) => current.get(index),
+        };
+        match next {
+            Some(value) => current = value,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assertion(json_path: Option<&str>, regex: Option<&str>) -> BodyAssertion {
+        BodyAssertion {
+            json_path: json_path.map(String::from),
+            regex: regex.map(String::from),
+        }
+    }
+
+    fn check(assertion: &BodyAssertion, body: &str) -> Result<()> {
+        BodyCheck::new(assertion).check(body)
+    }
+
+    #[test]
+    fn select_json_path() {
+        let document = json!({"result": {"items": [{"id": 1}, {"id": 2}], "a.b": true}});
+        assert_eq!(
+            select(&document, "$.result.items[1].id").unwrap(),
+            Some(&json!(2))
+        );
+        assert_eq!(
+            select(&document, "$['result']['a.b']").unwrap(),
+            Some(&json!(true))
+        );
+        assert_eq!(select(&document, "$").unwrap(), Some(&document));
+        assert_eq!(select(&document, "$.result.missing").unwrap(), None);
+        assert!(select(&document, "result").is_err());
+        assert!(select(&document, "$.result[x]").is_err());
+    }
+
+    #[test]
+    fn check_body_value() {
+        let body = r#"{"status": "scheduled", "count": 3}"#;
+        assert!(check(&assertion(Some("$.status"), None), body).is_ok());
+        assert!(check(&assertion(Some("$.status"), Some("^scheduled$")), body).is_ok());
+        assert!(check(&assertion(Some("$.count"), Some("^3$")), body).is_ok());
+        assert!(check(&assertion(Some("$.status"), Some("^failed$")), body).is_err());
+        assert!(check(&assertion(Some("$.error"), None), body).is_err());
+        assert!(check(&assertion(Some("$.status"), None), "not json").is_err());
+    }
+
+    #[test]
+    fn check_whole_body() {
+        assert!(check(&assertion(None, Some("OK")), "Status: OK").is_ok());
+        assert!(check(&assertion(None, Some("OK")), "Status: FAILED").is_err());
+        assert!(check(&assertion(None, Some("(")), "Status: OK").is_err());
+    }
+}
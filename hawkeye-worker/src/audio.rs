@@ -0,0 +1,286 @@
+//! Measures the level and the loudness of the audio, and finds the silences in it.
+//!
+//! Loudness follows ITU-R BS.1770 without gating, every window is measured on its own.
+
+use crate::metrics::{AUDIO_LOUDNESS, AUDIO_RMS_LEVEL, FOUND_SILENCE_COUNTER};
+use hawkeye_core::models::{AudioDetection, AudioMeasure, VideoMode};
+
+/// The audio branch of the pipeline converts the audio to this format before it's measured.
+pub const SAMPLE_RATE: usize = 48_000;
+pub const CHANNELS: usize = 2;
+
+/// The audio is measured in windows of 400ms, as the momentary loudness of EBU R 128.
+const WINDOW_MILLIS: u32 = 400;
+const WINDOW_FRAMES: usize = SAMPLE_RATE * WINDOW_MILLIS as usize / 1000;
+
+/// Level reported for digital silence.
+const MIN_LEVEL: f64 = -120.0;
+
+const DEFAULT_SILENCE_THRESHOLD: i32 = -50;
+const DEFAULT_SILENCE_DURATION: u32 = 2000;
+
+/// Levels of a window of audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    /// Root mean square level of all the channels, in dBFS.
+    pub rms: f64,
+    /// Momentary loudness, in LUFS.
+    pub loudness: f64,
+}
+
+/// Measures the audio of the source and finds the mode it's in.
+pub struct AudioDetector {
+    meter: LevelMeter,
+    silence: SilenceDetector,
+}
+
+impl AudioDetector {
+    pub fn new(settings: &AudioDetection) -> Self {
+        Self {
+            meter: LevelMeter::default(),
+            silence: SilenceDetector::new(settings),
+        }
+    }
+
+    /// Measures interleaved samples, returning the mode of the audio after every window they
+    /// complete.
+    pub fn detect(&mut self, samples: &[f32]) -> Vec<VideoMode> {
+        let mut modes = Vec::new();
+        for levels in self.meter.push(samples) {
+            AUDIO_RMS_LEVEL.set(levels.rms);
+            AUDIO_LOUDNESS.set(levels.loudness);
+            let mode = self.silence.update(&levels);
+            if mode == VideoMode::Silence {
+                FOUND_SILENCE_COUNTER.inc();
+            }
+            modes.push(mode);
+        }
+        modes
+    }
+}
+
+/// Second order IIR filter, in transposed direct form II.
+#[derive(Clone)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// K-weighting of BS.1770 at 48kHz: a high shelf accounting for the head, then a high pass.
+fn k_weighting() -> [Biquad; 2] {
+    [
+        Biquad::new(
+            1.535_124_859_586_97,
+            -2.691_696_189_406_38,
+            1.198_392_810_852_85,
+            -1.690_659_293_182_41,
+            0.732_480_774_215_85,
+        ),
+        Biquad::new(1.0, -2.0, 1.0, -1.990_047_454_833_98, 0.990_072_250_366_21),
+    ]
+}
+
+/// Measures the audio in windows of 400ms.
+pub struct LevelMeter {
+    filters: Vec<[Biquad; 2]>,
+    frames: usize,
+    sum_squares: f64,
+    weighted_sum_squares: [f64; CHANNELS],
+}
+
+impl Default for LevelMeter {
+    fn default() -> Self {
+        Self {
+            filters: vec![k_weighting(); CHANNELS],
+            frames: 0,
+            sum_squares: 0.0,
+            weighted_sum_squares: [0.0; CHANNELS],
+        }
+    }
+}
+
+impl LevelMeter {
+    /// Measures interleaved samples, returning the levels of every window they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Levels> {
+        let mut levels = Vec::new();
+        for frame in samples.chunks_exact(CHANNELS) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let sample = *sample as f64;
+                self.sum_squares += sample * sample;
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let weighted = high_pass.process(shelf.process(sample));
+                self.weighted_sum_squares[channel] += weighted * weighted;
+            }
+            self.frames += 1;
+            if self.frames == WINDOW_FRAMES {
+                levels.push(self.finish_window());
+            }
+        }
+        levels
+    }
+
+    fn finish_window(&mut self) -> Levels {
+        let frames = self.frames as f64;
+        let mean_square = self.sum_squares / (frames * CHANNELS as f64);
+        // Both channels have a weight of 1
+        let weighted: f64 = self
+            .weighted_sum_squares
+            .iter()
+            .map(|sum| sum / frames)
+            .sum();
+        self.frames = 0;
+        self.sum_squares = 0.0;
+        self.weighted_sum_squares = [0.0; CHANNELS];
+        Levels {
+            rms: decibels(mean_square),
+            loudness: (-0.691 + decibels(weighted)).max(MIN_LEVEL),
+        }
+    }
+}
+
+fn decibels(power: f64) -> f64 {
+    if power > 0.0 {
+        (10.0 * power.log10()).max(MIN_LEVEL)
+    } else {
+        MIN_LEVEL
+    }
+}
+
+/// Finds the silences, the audio is silent once it stays below the threshold for long enough.
+pub struct SilenceDetector {
+    measure: AudioMeasure,
+    threshold: f64,
+    /// Consecutive quiet windows that make a silence.
+    windows: u32,
+    quiet_windows: u32,
+}
+
+impl SilenceDetector {
+    pub fn new(settings: &AudioDetection) -> Self {
+        let duration = settings.duration.unwrap_or(DEFAULT_SILENCE_DURATION);
+        let threshold = settings
+            .silence_threshold
+            .unwrap_or(DEFAULT_SILENCE_THRESHOLD);
+        Self {
+            measure: settings.measure.unwrap_or(AudioMeasure::Rms),
+            threshold: threshold as f64,
+            windows: (duration as f64 / WINDOW_MILLIS as f64).ceil().max(1.0) as u32,
+            quiet_windows: 0,
+        }
+    }
+
+    /// Mode of the audio after the window.
+    pub fn update(&mut self, levels: &Levels) -> VideoMode {
+        let level = match self.measure {
+            AudioMeasure::Rms => levels.rms,
+            AudioMeasure::Lufs => levels.loudness,
+        };
+        if level < self.threshold {
+            self.quiet_windows = self.quiet_windows.saturating_add(1);
+        } else {
+            self.quiet_windows = 0;
+        }
+        if self.quiet_windows >= self.windows {
+            VideoMode::Silence
+        } else {
+            VideoMode::Audio
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// Stereo sine wave with the same samples in both channels.
+    fn sine(frequency: f64, amplitude: f64, millis: usize) -> Vec<f32> {
+        let frames = SAMPLE_RATE * millis / 1000;
+        let mut samples = Vec::with_capacity(frames * CHANNELS);
+        for i in 0..frames {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            let sample = (amplitude * (2.0 * PI * frequency * t).sin()) as f32;
+            samples.push(sample);
+            samples.push(sample);
+        }
+        samples
+    }
+
+    #[test]
+    fn measures_full_scale_sine() {
+        let mut meter = LevelMeter::default();
+        let levels = meter.push(&sine(1000.0, 1.0, 800));
+        assert_eq!(levels.len(), 2);
+        // The first window lets the filters settle
+        let levels = levels[1];
+        assert!((levels.rms - -3.01).abs() < 0.01, "{:?}", levels);
+        // Both channels at -3.01 LUFS add up to 0 LUFS
+        assert!(levels.loudness.abs() < 0.1, "{:?}", levels);
+    }
+
+    #[test]
+    fn measures_windows_across_pushes() {
+        let mut meter = LevelMeter::default();
+        let samples = sine(1000.0, 0.1, 600);
+        let (first, second) = samples.split_at(samples.len() / 2);
+        assert!(meter.push(first).is_empty());
+        let levels = meter.push(second);
+        assert_eq!(levels.len(), 1);
+        assert!((levels[0].rms - -23.01).abs() < 0.01, "{:?}", levels);
+
+        assert_eq!(
+            LevelMeter::default().push(&vec![0.0; WINDOW_FRAMES * CHANNELS]),
+            vec![Levels {
+                rms: MIN_LEVEL,
+                loudness: MIN_LEVEL,
+            }]
+        );
+    }
+
+    #[test]
+    fn silence_after_duration_below_threshold() {
+        let mut detector = AudioDetector::new(&AudioDetection {
+            measure: Some(AudioMeasure::Lufs),
+            silence_threshold: Some(-40),
+            duration: Some(1000),
+        });
+        assert_eq!(
+            detector.detect(&sine(1000.0, 0.5, 400)),
+            vec![VideoMode::Audio]
+        );
+        assert_eq!(
+            detector.detect(&sine(1000.0, 0.001, 1200)),
+            vec![VideoMode::Audio, VideoMode::Audio, VideoMode::Silence]
+        );
+        assert_eq!(
+            detector.detect(&sine(1000.0, 0.5, 400)),
+            vec![VideoMode::Audio]
+        );
+    }
+}
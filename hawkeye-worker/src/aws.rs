@@ -0,0 +1,246 @@
+//! Minimal implementation of the AWS Signature Version 4 signing process.
+//!
+//! See: https://docs.aws.amazon.com/general/latest/gr/signature-version-4.html
+
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// AWS credentials used to sign requests.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Loads the credentials from the standard AWS environment variables.
+    pub fn from_env() -> Result<Self> {
+        let access_key_id = std::env::var("AWS_ACCESS_KEY_ID")
+            .map_err(|_| eyre!("Missing AWS_ACCESS_KEY_ID environment variable"))?;
+        let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY")
+            .map_err(|_| eyre!("Missing AWS_SECRET_ACCESS_KEY environment variable"))?;
+        Ok(Self {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// A request to be signed.
+pub struct Request<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    /// Headers sent in the request, the `host` header is derived from the URL.
+    pub headers: &'a [(&'a str, &'a str)],
+    pub body: &'a [u8],
+}
+
+/// Signs the request, returning the headers that must be added to it.
+pub fn sign(
+    request: &Request,
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    time: DateTime<Utc>,
+) -> Result<Vec<(String, String)>> {
+    let (host, path, query) = split_url(request.url)?;
+    let amz_date = time.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = time.format("%Y%m%d").to_string();
+
+    let mut added_headers = vec![("x-amz-date".to_string(), amz_date.clone())];
+    if let Some(token) = credentials.session_token.as_ref() {
+        added_headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+        .chain(std::iter::once(("host".to_string(), host.to_string())))
+        .chain(added_headers.iter().cloned())
+        .collect();
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<&str>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        canonical_uri(path),
+        canonical_query(query),
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(request.body))
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let secret = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac_sha256(secret.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    added_headers.push((
+        "authorization".to_string(),
+        format!(
+            "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            ALGORITHM, credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    Ok(added_headers)
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Splits the URL in host (with port), path and query string.
+fn split_url(url: &str) -> Result<(&str, &str, &str)> {
+    let scheme_end = url
+        .find("://")
+        .ok_or_else(|| eyre!("{} not recognized as a valid URL!", url))?;
+    let rest = &url[scheme_end + 3..];
+    let (authority, path_and_query) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/"),
+    };
+    let (path, query) = match path_and_query.find('?') {
+        Some(idx) => (&path_and_query[..idx], &path_and_query[idx + 1..]),
+        None => (path_and_query, ""),
+    };
+    Ok((authority, path, query))
+}
+
+fn canonical_uri(path: &str) -> String {
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn canonical_query(query: &str) -> String {
+    let mut params: Vec<(String, String)> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.find('=') {
+            Some(idx) => (uri_encode(&param[..idx]), uri_encode(&param[idx + 1..])),
+            None => (uri_encode(param), String::new()),
+        })
+        .collect();
+    params.sort();
+    params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Percent-encodes everything except the unreserved characters.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from the AWS Signature Version 4 test suite
+    fn credentials() -> Credentials {
+        Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        }
+    }
+
+    fn authorization(headers: Vec<(String, String)>) -> String {
+        headers
+            .into_iter()
+            .find(|(k, _)| k == "authorization")
+            .map(|(_, v)| v)
+            .unwrap()
+    }
+
+    #[test]
+    fn sign_get_vanilla() {
+        let request = Request {
+            method: "GET",
+            url: "https://example.amazonaws.com/",
+            headers: &[],
+            body: b"",
+        };
+        let time = "2015-08-30T12:36:00Z".parse().unwrap();
+        let headers = sign(&request, &credentials(), "us-east-1", "service", time).unwrap();
+
+        assert_eq!(
+            authorization(headers),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn sign_get_vanilla_query_order() {
+        let request = Request {
+            method: "GET",
+            url: "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+            headers: &[],
+            body: b"",
+        };
+        let time = "2015-08-30T12:36:00Z".parse().unwrap();
+        let headers = sign(&request, &credentials(), "us-east-1", "service", time).unwrap();
+
+        assert_eq!(
+            authorization(headers),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+    }
+
+    #[test]
+    fn sign_post_with_body() {
+        let request = Request {
+            method: "POST",
+            url: "https://example.amazonaws.com/",
+            headers: &[("Content-Type", "application/x-www-form-urlencoded")],
+            body: b"Param1=value1",
+        };
+        let time = "2015-08-30T12:36:00Z".parse().unwrap();
+        let headers = sign(&request, &credentials(), "us-east-1", "service", time).unwrap();
+
+        assert_eq!(
+            authorization(headers),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a"
+        );
+    }
+}
@@ -10,4 +10,7 @@ pub struct AppConfig {
     // Path to the watcher configuration
     #[structopt(parse(from_os_str))]
     pub watcher_path: PathBuf,
+    // Directory where the Kubernetes Secrets referenced by the watcher are mounted
+    #[structopt(long, parse(from_os_str), default_value = "/secrets")]
+    pub secrets_path: PathBuf,
 }
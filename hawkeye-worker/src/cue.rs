@@ -0,0 +1,112 @@
+//! Cross-checks the SCTE-35 cues of the input against the slates found in the video: the program
+//! is expected to show a slate between a `cue_out` and the next `cue_in`.
+
+use crate::metrics::SCTE35_DISAGREEMENT_COUNTER;
+use hawkeye_core::models::{ModeKind, VideoMode};
+use log::warn;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+const DEFAULT_DISAGREEMENT_TIMEOUT: u32 = 5000;
+
+/// Counts the times the cues and the video disagree for longer than the timeout, which leaves
+/// time for the slate to show up after the splice.
+pub struct CueAgreement {
+    timeout: Duration,
+    cue: Option<VideoMode>,
+    video: VideoMode,
+    disagreeing_since: Option<Instant>,
+    /// The disagreement is counted once, until the cues and the video agree again.
+    reported: bool,
+}
+
+impl CueAgreement {
+    pub fn new(timeout: Option<u32>) -> Self {
+        let timeout = timeout.unwrap_or(DEFAULT_DISAGREEMENT_TIMEOUT);
+        Self {
+            timeout: Duration::from_millis(timeout as u64),
+            cue: None,
+            video: VideoMode::Unknown,
+            disagreeing_since: None,
+            reported: false,
+        }
+    }
+
+    /// Records a mode detected, returning true when the disagreement it continues is counted.
+    pub fn update(&mut self, mode: VideoMode) -> bool {
+        match mode.kind() {
+            ModeKind::Cue => self.cue = Some(mode),
+            ModeKind::Video => self.video = mode,
+            _ => return false,
+        }
+        // Black, frozen or missing video says nothing about the break
+        let disagree = match (self.cue, self.video) {
+            (Some(VideoMode::CueOut), VideoMode::Content) => true,
+            (Some(VideoMode::CueIn), VideoMode::Slate) => true,
+            _ => false,
+        };
+        if !disagree {
+            self.disagreeing_since = None;
+            self.reported = false;
+            return false;
+        }
+        let since = *self.disagreeing_since.get_or_insert_with(Instant::now);
+        if self.reported || since.elapsed() < self.timeout {
+            return false;
+        }
+        self.reported = true;
+        SCTE35_DISAGREEMENT_COUNTER.inc();
+        warn!(
+            "The SCTE-35 cues signal {} but the video has been in {} for {}ms",
+            self.cue.map_or("", |cue| cue.name()),
+            self.video.name(),
+            since.elapsed().as_millis()
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_fake_clock::FakeClock;
+
+    #[test]
+    fn counts_disagreement_after_timeout() {
+        let mut agreement = CueAgreement::new(Some(2000));
+        assert!(!agreement.update(VideoMode::Content));
+        assert!(!agreement.update(VideoMode::CueOut));
+        FakeClock::advance_time(1999);
+        assert!(!agreement.update(VideoMode::Content));
+        FakeClock::advance_time(1);
+        assert!(agreement.update(VideoMode::Content));
+        // Counted once
+        FakeClock::advance_time(1000);
+        assert!(!agreement.update(VideoMode::Content));
+        assert!(!agreement.update(VideoMode::Silence));
+
+        assert!(!agreement.update(VideoMode::Slate));
+        assert!(!agreement.update(VideoMode::CueIn));
+        FakeClock::advance_time(2000);
+        assert!(agreement.update(VideoMode::Slate));
+    }
+
+    #[test]
+    fn agreement_resets_timeout() {
+        let mut agreement = CueAgreement::new(None);
+        assert!(!agreement.update(VideoMode::CueOut));
+        assert!(!agreement.update(VideoMode::Content));
+        FakeClock::advance_time(4000);
+        assert!(!agreement.update(VideoMode::Slate));
+        assert!(!agreement.update(VideoMode::Content));
+        FakeClock::advance_time(4000);
+        assert!(!agreement.update(VideoMode::Black));
+        assert!(!agreement.update(VideoMode::Content));
+        FakeClock::advance_time(5000);
+        assert!(agreement.update(VideoMode::Content));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::metrics::MODE_CHANGE_SUPPRESSED_COUNTER;
+use hawkeye_core::models::{Debounce, VideoMode};
+use log::debug;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// Video mode found in a frame, with the label of the slate when one was found.
+pub type Detection = (VideoMode, Option<String>);
+
+/// A detection waiting for enough agreement to be confirmed.
+struct Candidate {
+    detection: Detection,
+    frames: u32,
+    since: Instant,
+}
+
+/// Confirms a new video mode only after it was detected in enough consecutive frames and for
+/// long enough, filtering out flips caused by misdetected frames.
+pub struct ModeDebouncer {
+    frames: u32,
+    duration: Duration,
+    confirmed: Option<Detection>,
+    candidate: Option<Candidate>,
+}
+
+impl ModeDebouncer {
+    pub fn new(settings: &Debounce) -> Self {
+        Self {
+            frames: settings.frames.unwrap_or(1),
+            duration: Duration::from_millis(settings.duration.unwrap_or(0) as u64),
+            confirmed: None,
+            candidate: None,
+        }
+    }
+
+    /// Feeds the detection of a frame, returning the confirmed detection if there is one.
+    pub fn update(&mut self, mode: VideoMode, slate: Option<String>) -> Option<Detection> {
+        let detection = (mode, slate);
+        if self.confirmed.as_ref() == Some(&detection) {
+            self.discard_candidate();
+            return self.confirmed.clone();
+        }
+
+        let is_same_candidate = self
+            .candidate
+            .as_ref()
+            .map_or(false, |c| c.detection == detection);
+        if is_same_candidate {
+            if let Some(candidate) = self.candidate.as_mut() {
+                candidate.frames += 1;
+            }
+        } else {
+            self.discard_candidate();
+            self.candidate = Some(Candidate {
+                detection,
+                frames: 1,
+                since: Instant::now(),
+            });
+        }
+
+        let is_confirmed = self.candidate.as_ref().map_or(false, |c| {
+            c.frames >= self.frames && c.since.elapsed() >= self.duration
+        });
+        if is_confirmed {
+            self.confirmed = self.candidate.take().map(|c| c.detection);
+            debug!("Video mode changed to {:?}", self.confirmed);
+        }
+        self.confirmed.clone()
+    }
+
+    /// Forgets the confirmed mode, so it has to be confirmed again. Used when the signal is lost.
+    pub fn reset(&mut self) {
+        self.confirmed = None;
+        self.candidate = None;
+    }
+
+    fn discard_candidate(&mut self) {
+        if let Some(candidate) = self.candidate.take() {
+            debug!(
+                "Ignored change to {:?} after {} frames",
+                candidate.detection, candidate.frames
+            );
+            MODE_CHANGE_SUPPRESSED_COUNTER.inc();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_fake_clock::FakeClock;
+
+    const CONTENT: Detection = (VideoMode::Content, None);
+
+    fn slate() -> Detection {
+        (VideoMode::Slate, Some("ad-break".to_string()))
+    }
+
+    #[test]
+    fn confirms_every_mode_without_settings() {
+        let mut debouncer = ModeDebouncer::new(&Debounce::default());
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+        assert_eq!(
+            debouncer.update(VideoMode::Slate, Some("ad-break".to_string())),
+            Some(slate())
+        );
+    }
+
+    #[test]
+    fn confirms_mode_after_consecutive_frames() {
+        let mut debouncer = ModeDebouncer::new(&Debounce {
+            frames: Some(3),
+            duration: None,
+        });
+        assert_eq!(debouncer.update(VideoMode::Content, None), None);
+        assert_eq!(debouncer.update(VideoMode::Content, None), None);
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+
+        // A single misdetected frame does not change the mode
+        assert_eq!(
+            debouncer.update(VideoMode::Slate, Some("ad-break".to_string())),
+            Some(CONTENT)
+        );
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+
+        for _ in 0..2 {
+            assert_eq!(
+                debouncer.update(VideoMode::Slate, Some("ad-break".to_string())),
+                Some(CONTENT)
+            );
+        }
+        assert_eq!(
+            debouncer.update(VideoMode::Slate, Some("ad-break".to_string())),
+            Some(slate())
+        );
+    }
+
+    #[test]
+    fn confirms_mode_after_duration() {
+        let mut debouncer = ModeDebouncer::new(&Debounce {
+            frames: None,
+            duration: Some(500),
+        });
+        assert_eq!(debouncer.update(VideoMode::Content, None), None);
+        FakeClock::advance_time(499);
+        assert_eq!(debouncer.update(VideoMode::Content, None), None);
+        FakeClock::advance_time(1);
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+    }
+
+    #[test]
+    fn reset_requires_mode_to_be_confirmed_again() {
+        let mut debouncer = ModeDebouncer::new(&Debounce {
+            frames: Some(2),
+            duration: None,
+        });
+        debouncer.update(VideoMode::Content, None);
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+
+        debouncer.reset();
+        assert_eq!(debouncer.update(VideoMode::Content, None), None);
+        assert_eq!(debouncer.update(VideoMode::Content, None), Some(CONTENT));
+    }
+
+    #[test]
+    fn slate_label_change_is_a_mode_change() {
+        let mut debouncer = ModeDebouncer::new(&Debounce {
+            frames: Some(2),
+            duration: None,
+        });
+        debouncer.update(VideoMode::Slate, Some("ad-break".to_string()));
+        assert_eq!(
+            debouncer.update(VideoMode::Slate, Some("ad-break".to_string())),
+            Some(slate())
+        );
+        assert_eq!(
+            debouncer.update(VideoMode::Slate, Some("sponsor".to_string())),
+            Some(slate())
+        );
+    }
+}
@@ -1,69 +1,544 @@
-use color_eyre::Result;
+use crate::debounce::Detection;
+use crate::metrics::PERCEPTUAL_HASH_DECIDED_COUNTER;
+use crate::phash;
+use color_eyre::{eyre::eyre, Result};
 use dssim::{DssimImage, ToRGBAPLU, RGBAPLU};
+use hawkeye_core::models::{
+    BlackFrameDetection, ColorMode, DetectorSettings, FrozenFrameDetection, HashAlgorithm,
+    PerceptualHash, Region, RegionMode, Slate, VideoMode,
+};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageOutputFormat};
 use imgref::{Img, ImgVec};
 use load_image::{Image, ImageData};
 use std::io::Read;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// Default maximum dissimilarity score for a frame to be considered a slate.
+pub const DEFAULT_THRESHOLD: u32 = 900;
+
+/// Size the frames are scaled to when there are no slates and no resolution is configured.
+const DEFAULT_RESOLUTION: (usize, usize) = (160, 90);
+
+const DEFAULT_BLACK_PIXEL_THRESHOLD: u8 = 10;
+const DEFAULT_BLACK_RATIO: u8 = 98;
+const DEFAULT_FROZEN_DURATION: u32 = 10;
+const DEFAULT_FROZEN_MAX_DIFFERENCE: u8 = 1;
+
+/// Recognizes a video mode in the frames.
+pub trait Detector: Send {
+    /// Mode the frame is in, `None` when it's not the mode the detector looks for.
+    fn detect(&mut self, frame: &Frame) -> Option<Detection>;
+}
+
+/// Runs the detectors in order, the first one recognizing the frame decides its mode. Frames no
+/// detector recognizes are content.
+pub struct Detectors {
+    width: usize,
+    height: usize,
+    frame_interval: Option<u32>,
+    detectors: Vec<Box<dyn Detector>>,
+}
+
+impl Detectors {
+    /// Slates are matched first, then black frames and then frozen frames, since slates and black
+    /// frames are usually frozen too.
+    pub fn new<R: Read>(slates: Vec<(Slate, R)>, settings: &DetectorSettings) -> Result<Self> {
+        let mut detectors: Vec<Box<dyn Detector>> = Vec::new();
+        let mut size = settings
+            .resolution
+            .map(|r| (r.width as usize, r.height as usize));
+        if !slates.is_empty() {
+            let detector = SlateDetector::new(slates, settings)?;
+            size = Some(detector.required_image_size());
+            detectors.push(Box::new(detector));
+        }
+        if let Some(black) = settings.black_frame.as_ref() {
+            detectors.push(Box::new(BlackFrameDetector::new(black)));
+        }
+        if let Some(frozen) = settings.frozen_frame.as_ref() {
+            detectors.push(Box::new(FrozenFrameDetector::new(frozen)));
+        }
+        if detectors.is_empty() {
+            return Err(eyre!(
+                "At least one slate, or the black or frozen frame detection, is required"
+            ));
+        }
+
+        let (width, height) = size.unwrap_or(DEFAULT_RESOLUTION);
+        Ok(Self {
+            width,
+            height,
+            frame_interval: settings.frame_interval,
+            detectors,
+        })
+    }
+
+    /// Decodes the frame and finds the mode it's in.
+    pub fn detect(&mut self, image_buffer: &[u8]) -> Result<Detection> {
+        let frame = Frame::decode(image_buffer)?;
+        let mut found = None;
+        // Every detector sees every frame, so detectors following the video over time are not
+        // interrupted by the ones before them
+        for detector in self.detectors.iter_mut() {
+            let detection = detector.detect(&frame);
+            if found.is_none() {
+                found = detection;
+            }
+        }
+        Ok(found.unwrap_or((VideoMode::Content, None)))
+    }
+
+    /// The size frames must have to be given to the detectors.
+    pub fn required_image_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Milliseconds between the frames to give to the detectors, `None` for every frame.
+    pub fn frame_interval(&self) -> Option<u32> {
+        self.frame_interval
+    }
+}
+
+/// Decoded video frame, shared by the detectors.
+pub struct Frame {
+    bitmap: Bitmap,
+}
+
+impl Frame {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            bitmap: load_data(data, ColorMode::Color)?,
+        })
+    }
+
+    /// Perceived brightness of every pixel, from 0 to 1. Pixels are in linear light, where dark
+    /// colors are crowded near 0.
+    fn luma(&self) -> impl Iterator<Item = f32> + '_ {
+        self.bitmap
+            .pixels
+            .iter()
+            .map(|px| luminance(px).powf(1.0 / 2.2))
+    }
+}
+
+/// A slate image prepared to be compared with the video frames.
+pub struct ReferenceSlate {
+    pub label: Option<String>,
+    threshold: u32,
+    mask: Option<Mask>,
+    /// Perceptual hash of the masked slate, with the algorithm of the prefilter.
+    hash: u64,
+    image: DssimImage<f32>,
+}
+
+/// Decides with the perceptual hashes whether the frames match the slates, when they are clearly
+/// similar or clearly different.
+struct HashPrefilter {
+    algorithm: HashAlgorithm,
+    match_distance: u32,
+    mismatch_distance: u32,
+}
+
+impl HashPrefilter {
+    fn new(settings: &PerceptualHash) -> Self {
+        Self {
+            algorithm: settings.algorithm.unwrap_or(HashAlgorithm::Dhash),
+            match_distance: settings
+                .match_distance
+                .unwrap_or(PerceptualHash::DEFAULT_MATCH_DISTANCE),
+            mismatch_distance: settings
+                .mismatch_distance
+                .unwrap_or(PerceptualHash::DEFAULT_MISMATCH_DISTANCE),
+        }
+    }
+
+    fn hash(&self, bitmap: &Bitmap) -> u64 {
+        let luma: Vec<f32> = bitmap.pixels.iter().map(luminance).collect();
+        phash::hash(self.algorithm, &luma, bitmap.width, bitmap.height)
+    }
+}
 
 pub struct SlateDetector {
     width: usize,
     height: usize,
-    slate: DssimImage<f32>,
+    color_mode: ColorMode,
+    slates: Vec<ReferenceSlate>,
+    prefilter: Option<HashPrefilter>,
     similarity_algorithm: dssim::Dssim,
 }
 
 impl SlateDetector {
-    pub fn new<R: Read>(slate: &mut R) -> Result<Self> {
-        let mut buffer = Vec::new();
-        slate.read_to_end(&mut buffer).unwrap();
+    /// Creates a detector for the slates, every slate is scaled to the comparison resolution or
+    /// to the size of the first slate.
+    pub fn new<R: Read>(slates: Vec<(Slate, R)>, settings: &DetectorSettings) -> Result<Self> {
+        let color_mode = settings.color_mode.unwrap_or(ColorMode::Color);
+        let default_threshold = settings.threshold.unwrap_or(DEFAULT_THRESHOLD);
         let similarity_algorithm = dssim::Dssim::new();
-        let slate_img = load_data(buffer.as_slice())?;
-        let slate = similarity_algorithm.create_image(&slate_img).unwrap();
+        let prefilter = settings.perceptual_hash.as_ref().map(HashPrefilter::new);
+        // Slates are hashed even without a prefilter, it's cheap next to loading them
+        let hasher = HashPrefilter::new(&settings.perceptual_hash.unwrap_or_default());
+        let mut size = settings
+            .resolution
+            .map(|r| (r.width as usize, r.height as usize));
 
+        let mut references = Vec::with_capacity(slates.len());
+        for (slate, mut reader) in slates.into_iter() {
+            let mut buffer = Vec::new();
+            reader.read_to_end(&mut buffer)?;
+            let mut slate_img = load_data(buffer.as_slice(), color_mode)?;
+            match size {
+                None => size = Some((slate_img.width, slate_img.height)),
+                Some((width, height)) if slate_img.width != width || slate_img.height != height => {
+                    let resized = resize(buffer.as_slice(), width as u32, height as u32)?;
+                    slate_img = load_data(resized.as_slice(), color_mode)?;
+                }
+                _ => {}
+            }
+            let mask = slate
+                .regions
+                .as_ref()
+                .map(|regions| Mask::new(regions, slate_img.width, slate_img.height));
+            if let Some(mask) = mask.as_ref() {
+                mask.apply(&mut slate_img.pixels);
+            }
+            references.push(ReferenceSlate {
+                label: slate.label,
+                threshold: slate.threshold.unwrap_or(default_threshold),
+                mask,
+                hash: hasher.hash(&slate_img),
+                image: similarity_algorithm
+                    .create_image(&slate_img.into_img())
+                    .unwrap(),
+            });
+        }
+
+        let (width, height) = size.ok_or_else(|| eyre!("At least one slate is required"))?;
         Ok(Self {
-            width: slate_img.width(),
-            height: slate_img.height(),
-            slate,
+            width,
+            height,
+            color_mode,
+            slates: references,
+            prefilter,
             similarity_algorithm,
         })
     }
 
-    pub fn is_match(&self, image_buffer: &[u8]) -> bool {
-        let frame_img = load_data(image_buffer).unwrap();
-        let frame = self.similarity_algorithm.create_image(&frame_img).unwrap();
+    /// Compares the frame with every slate, returning the most similar slate within its
+    /// threshold.
+    ///
+    /// Slates with regions are compared with a copy of the frame masked the same way as the slate.
+    /// With a prefilter, the slate with the closest hash is found without DSSIM when the hashes
+    /// match, and DSSIM only compares the frame with the slates the hashes can't tell apart.
+    pub fn find_slate(&self, frame: &Frame) -> Option<&ReferenceSlate> {
+        let mut bitmap = frame.bitmap.clone();
+        if self.color_mode == ColorMode::Grayscale {
+            to_grayscale(&mut bitmap.pixels);
+        }
+        let candidates = match self.prefilter.as_ref() {
+            Some(prefilter) => match self.prefilter_slates(prefilter, &bitmap) {
+                Ok(slate) => return Some(slate),
+                Err(candidates) => candidates,
+            },
+            None => self.slates.iter().collect(),
+        };
+        let mut unmasked_frame = None;
 
-        let (res, _) = self.similarity_algorithm.compare(&self.slate, frame);
-        let val: f64 = res.into();
-        let val = (val * 1000f64) as u32;
+        let mut found: Option<(u32, &ReferenceSlate)> = None;
+        for slate in candidates {
+            let val = match slate.mask.as_ref() {
+                Some(mask) => {
+                    let mut masked = bitmap.clone();
+                    mask.apply(&mut masked.pixels);
+                    self.dissimilarity(&slate.image, &self.create_image(masked))
+                }
+                None => {
+                    let frame =
+                        unmasked_frame.get_or_insert_with(|| self.create_image(bitmap.clone()));
+                    self.dissimilarity(&slate.image, frame)
+                }
+            };
+            let most_similar = found.map_or(true, |(found_val, _)| val < found_val);
+            if val <= slate.threshold && most_similar {
+                found = Some((val, slate));
+            }
+        }
+        found.map(|(_, slate)| slate)
+    }
 
-        val <= 900u32
+    /// Compares the hashes of the frame and the slates, returning the closest slate matching the
+    /// frame, or the slates DSSIM must compare the frame with when none does.
+    fn prefilter_slates(
+        &self,
+        prefilter: &HashPrefilter,
+        bitmap: &Bitmap,
+    ) -> std::result::Result<&ReferenceSlate, Vec<&ReferenceSlate>> {
+        let mut unmasked_hash = None;
+        let mut candidates = Vec::new();
+        let mut closest: Option<(u32, &ReferenceSlate)> = None;
+        for slate in self.slates.iter() {
+            let hash = match slate.mask.as_ref() {
+                Some(mask) => {
+                    let mut masked = bitmap.clone();
+                    mask.apply(&mut masked.pixels);
+                    prefilter.hash(&masked)
+                }
+                None => *unmasked_hash.get_or_insert_with(|| prefilter.hash(bitmap)),
+            };
+            let distance = phash::distance(hash, slate.hash);
+            if distance <= prefilter.match_distance {
+                if closest.map_or(true, |(closest, _)| distance < closest) {
+                    closest = Some((distance, slate));
+                }
+            } else if distance < prefilter.mismatch_distance {
+                candidates.push(slate);
+            }
+        }
+        // Counted once per frame, when DSSIM doesn't run at all
+        match closest {
+            Some((_, slate)) => {
+                PERCEPTUAL_HASH_DECIDED_COUNTER.inc();
+                Ok(slate)
+            }
+            None => {
+                if candidates.is_empty() {
+                    PERCEPTUAL_HASH_DECIDED_COUNTER.inc();
+                }
+                Err(candidates)
+            }
+        }
     }
 
+    /// The size frames must have to be compared with the slates.
     pub fn required_image_size(&self) -> (usize, usize) {
         (self.width, self.height)
     }
+
+    fn create_image(&self, bitmap: Bitmap) -> DssimImage<f32> {
+        self.similarity_algorithm
+            .create_image(&bitmap.into_img())
+            .unwrap()
+    }
+
+    /// DSSIM score multiplied by 1000, zero means the images are equal.
+    fn dissimilarity(&self, slate: &DssimImage<f32>, frame: &DssimImage<f32>) -> u32 {
+        let (res, _) = self.similarity_algorithm.compare(slate, frame);
+        let val: f64 = res.into();
+        (val * 1000f64) as u32
+    }
+}
+
+impl Detector for SlateDetector {
+    fn detect(&mut self, frame: &Frame) -> Option<Detection> {
+        self.find_slate(frame)
+            .map(|slate| (VideoMode::Slate, slate.label.clone()))
+    }
+}
+
+/// Recognizes black frames, where almost every pixel is dark.
+pub struct BlackFrameDetector {
+    /// Maximum luminance of a dark pixel.
+    pixel_threshold: f32,
+    /// Minimum fraction of dark pixels.
+    ratio: f32,
+}
+
+impl BlackFrameDetector {
+    pub fn new(settings: &BlackFrameDetection) -> Self {
+        let pixel_threshold = settings
+            .pixel_threshold
+            .unwrap_or(DEFAULT_BLACK_PIXEL_THRESHOLD);
+        let ratio = settings.ratio.unwrap_or(DEFAULT_BLACK_RATIO);
+        Self {
+            pixel_threshold: pixel_threshold as f32 / 100.0,
+            ratio: ratio as f32 / 100.0,
+        }
+    }
+}
+
+impl Detector for BlackFrameDetector {
+    fn detect(&mut self, frame: &Frame) -> Option<Detection> {
+        let pixels = frame.bitmap.pixels.len();
+        let dark = frame
+            .luma()
+            .filter(|luma| *luma <= self.pixel_threshold)
+            .count();
+        if pixels > 0 && dark as f32 >= pixels as f32 * self.ratio {
+            Some((VideoMode::Black, None))
+        } else {
+            None
+        }
+    }
+}
+
+/// Recognizes frozen video, when the frames barely change for long enough. Frames are compared
+/// with the first frame of the freeze, so slow changes add up.
+pub struct FrozenFrameDetector {
+    duration: Duration,
+    /// Maximum mean difference of luminance between frames considered the same.
+    max_difference: f32,
+    /// Luminance of the first frame of the freeze, and when it arrived.
+    still: Option<(Vec<f32>, Instant)>,
+}
+
+impl FrozenFrameDetector {
+    pub fn new(settings: &FrozenFrameDetection) -> Self {
+        let duration = settings.duration.unwrap_or(DEFAULT_FROZEN_DURATION);
+        let max_difference = settings
+            .max_difference
+            .unwrap_or(DEFAULT_FROZEN_MAX_DIFFERENCE);
+        Self {
+            duration: Duration::from_secs(duration as u64),
+            max_difference: max_difference as f32 / 100.0,
+            still: None,
+        }
+    }
+}
+
+impl Detector for FrozenFrameDetector {
+    fn detect(&mut self, frame: &Frame) -> Option<Detection> {
+        let luma: Vec<f32> = frame.luma().collect();
+        let since = match self.still.as_ref() {
+            Some((first, since)) if mean_difference(first, &luma) <= self.max_difference => *since,
+            _ => {
+                self.still = Some((luma, Instant::now()));
+                return None;
+            }
+        };
+        if since.elapsed() >= self.duration {
+            Some((VideoMode::Frozen, None))
+        } else {
+            None
+        }
+    }
+}
+
+/// Mean absolute difference between the pixels of two frames of the same size.
+fn mean_difference(a: &[f32], b: &[f32]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return f32::MAX;
+    }
+    let total: f32 = a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).sum();
+    total / a.len() as f32
+}
+
+/// Pixels of the frame that take part in the comparison with a slate.
+struct Mask {
+    included: Vec<bool>,
+}
+
+impl Mask {
+    /// Builds the mask from the regions, when there are no include regions the whole frame is
+    /// included. Exclude regions take precedence over include regions and regions are clipped
+    /// to the frame size.
+    fn new(regions: &[Region], width: usize, height: usize) -> Self {
+        let has_includes = regions.iter().any(|r| r.mode == RegionMode::Include);
+        let mut included = vec![!has_includes; width * height];
+
+        for mode in [RegionMode::Include, RegionMode::Exclude].iter() {
+            for region in regions.iter().filter(|r| r.mode == *mode) {
+                let x_end = width.min(region.x.saturating_add(region.width) as usize);
+                let y_end = height.min(region.y.saturating_add(region.height) as usize);
+                for y in (region.y as usize)..y_end {
+                    for x in (region.x as usize)..x_end {
+                        included[y * width + x] = *mode == RegionMode::Include;
+                    }
+                }
+            }
+        }
+
+        Self { included }
+    }
+
+    /// Paints the pixels outside of the mask black, so they are equal in the frame and slate.
+    fn apply(&self, pixels: &mut [RGBAPLU]) {
+        for (px, included) in pixels.iter_mut().zip(self.included.iter()) {
+            if !included {
+                *px = RGBAPLU {
+                    r: 0.0,
+                    g: 0.0,
+                    b: 0.0,
+                    a: 1.0,
+                };
+            }
+        }
+    }
+}
+
+/// Decoded pixels of an image.
+#[derive(Clone)]
+struct Bitmap {
+    pixels: Vec<RGBAPLU>,
+    width: usize,
+    height: usize,
+}
+
+impl Bitmap {
+    fn into_img(self) -> ImgVec<RGBAPLU> {
+        Img::new(self.pixels, self.width, self.height)
+    }
 }
 
-fn load_data(data: &[u8]) -> Result<ImgVec<RGBAPLU>> {
+/// Scales the image to the comparison resolution, the result is encoded as PNG.
+fn resize(data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(data)?;
+    let resized = image::imageops::resize(&img, width, height, FilterType::Triangle);
+    let mut buffer = Vec::new();
+    DynamicImage::ImageRgba8(resized).write_to(&mut buffer, ImageOutputFormat::Png)?;
+    Ok(buffer)
+}
+
+fn load_data(data: &[u8], color_mode: ColorMode) -> Result<Bitmap> {
     let img = load_image::load_image_data(data, false)?;
-    Ok(match_img_bitmap(img))
+    let mut pixels = match_img_bitmap(&img);
+    if color_mode == ColorMode::Grayscale {
+        to_grayscale(&mut pixels);
+    }
+    Ok(Bitmap {
+        pixels,
+        width: img.width,
+        height: img.height,
+    })
 }
 
-fn match_img_bitmap(img: Image) -> ImgVec<RGBAPLU> {
+fn match_img_bitmap(img: &Image) -> Vec<RGBAPLU> {
     match img.bitmap {
-        ImageData::RGB8(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::RGB16(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::RGBA8(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::RGBA16(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::GRAY8(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::GRAY16(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::GRAYA8(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
-        ImageData::GRAYA16(ref bitmap) => Img::new(bitmap.to_rgbaplu(), img.width, img.height),
+        ImageData::RGB8(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::RGB16(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::RGBA8(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::RGBA16(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::GRAY8(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::GRAY16(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::GRAYA8(ref bitmap) => bitmap.to_rgbaplu(),
+        ImageData::GRAYA16(ref bitmap) => bitmap.to_rgbaplu(),
+    }
+}
+
+/// Luminance of the pixel, pixels are already in linear light.
+fn luminance(px: &RGBAPLU) -> f32 {
+    px.r * 0.2126 + px.g * 0.7152 + px.b * 0.0722
+}
+
+/// Replaces the color of every pixel by its luminance.
+fn to_grayscale(pixels: &mut [RGBAPLU]) {
+    for px in pixels.iter_mut() {
+        let luma = luminance(px);
+        px.r = luma;
+        px.g = luma;
+        px.b = luma;
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use hawkeye_core::models::Resolution;
+    use sn_fake_clock::FakeClock;
     use std::fs::File;
     use std::io::Read;
     use std::path::Path;
@@ -76,23 +551,308 @@ mod test {
         buffer
     }
 
+    fn decode(data: &[u8]) -> Frame {
+        Frame::decode(data).unwrap()
+    }
+
+    /// Frame of the given size where every pixel has the luminance.
+    fn solid_frame(luma: f32, width: usize, height: usize) -> Frame {
+        let px = RGBAPLU {
+            r: luma,
+            g: luma,
+            b: luma,
+            a: 1.0,
+        };
+        Frame {
+            bitmap: Bitmap {
+                pixels: vec![px; width * height],
+                width,
+                height,
+            },
+        }
+    }
+
+    fn slate(path: &str, label: &str, threshold: Option<u32>) -> (Slate, File) {
+        let file = File::open(path).expect("Missing file in resources folder");
+        let slate = Slate {
+            url: format!("file://{}", path),
+            label: Some(label.to_string()),
+            threshold,
+            regions: None,
+        };
+        (slate, file)
+    }
+
     #[test]
     fn compare_equal_images() {
-        let mut slate =
-            File::open("../resources/slate_120px.jpg").expect("Missing file in resources folder");
-        let detector = SlateDetector::new(&mut slate).unwrap();
+        let detector = SlateDetector::new(
+            vec![slate("../resources/slate_120px.jpg", "slate", None)],
+            &DetectorSettings::default(),
+        )
+        .unwrap();
         let slate_img = read_bytes("../resources/slate_120px.jpg");
 
-        assert!(detector.is_match(slate_img.as_slice()));
+        assert!(detector.find_slate(&decode(&slate_img)).is_some());
     }
 
     #[test]
     fn compare_diff_images() {
-        let mut slate =
-            File::open("../resources/slate_120px.jpg").expect("Missing file in resources folder");
-        let detector = SlateDetector::new(&mut slate).unwrap();
+        let detector = SlateDetector::new(
+            vec![slate("../resources/slate_120px.jpg", "slate", None)],
+            &DetectorSettings::default(),
+        )
+        .unwrap();
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+
+        assert!(detector.find_slate(&decode(&frame_img)).is_none());
+    }
+
+    #[test]
+    fn compare_diff_images_with_loose_threshold() {
+        let settings = DetectorSettings {
+            threshold: Some(u32::MAX),
+            ..Default::default()
+        };
+        let detector = SlateDetector::new(
+            vec![slate("../resources/slate_120px.jpg", "slate", None)],
+            &settings,
+        )
+        .unwrap();
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+
+        assert!(detector.find_slate(&decode(&frame_img)).is_some());
+    }
+
+    #[test]
+    fn compare_images_in_configured_resolution_and_grayscale() {
+        let settings = DetectorSettings {
+            threshold: None,
+            resolution: Some(Resolution {
+                width: 64,
+                height: 36,
+            }),
+            color_mode: Some(ColorMode::Grayscale),
+            ..Default::default()
+        };
+        let detector = SlateDetector::new(
+            vec![slate("../resources/slate_120px.jpg", "slate", None)],
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(detector.required_image_size(), (64, 36));
+
+        let slate_img = resize(&read_bytes("../resources/slate_120px.jpg"), 64, 36).unwrap();
+        assert!(detector.find_slate(&decode(&slate_img)).is_some());
+
+        let frame_img = resize(&read_bytes("../resources/non-slate_120px.jpg"), 64, 36).unwrap();
+        assert!(detector.find_slate(&decode(&frame_img)).is_none());
+    }
+
+    #[test]
+    fn reports_which_slate_matched() {
+        let detector = SlateDetector::new(
+            vec![
+                // Would match any frame, but is never the most similar to the slate
+                slate("../resources/non-slate_120px.jpg", "other", Some(u32::MAX)),
+                slate("../resources/slate_120px.jpg", "ad-break", None),
+            ],
+            &DetectorSettings::default(),
+        )
+        .unwrap();
+
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        let found = detector.find_slate(&decode(&slate_img)).unwrap();
+        assert_eq!(found.label.as_deref(), Some("ad-break"));
+
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+        let found = detector.find_slate(&decode(&frame_img)).unwrap();
+        assert_eq!(found.label.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn perceptual_hash_decides_without_dssim() {
+        let prefiltered = |threshold, match_distance, mismatch_distance| {
+            let settings = DetectorSettings {
+                threshold: Some(threshold),
+                perceptual_hash: Some(PerceptualHash {
+                    algorithm: Some(HashAlgorithm::Phash),
+                    match_distance: Some(match_distance),
+                    mismatch_distance: Some(mismatch_distance),
+                }),
+                ..Default::default()
+            };
+            SlateDetector::new(
+                vec![slate("../resources/slate_120px.jpg", "slate", None)],
+                &settings,
+            )
+            .unwrap()
+        };
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
         let frame_img = read_bytes("../resources/non-slate_120px.jpg");
 
-        assert_eq!(detector.is_match(frame_img.as_slice()), false);
+        // Only DSSIM would reject the frames, or match the content
+        let detector = prefiltered(0, 63, 64);
+        assert!(detector.find_slate(&decode(&frame_img)).is_some());
+        let detector = prefiltered(u32::MAX, 0, 1);
+        assert!(detector.find_slate(&decode(&slate_img)).is_some());
+        assert!(detector.find_slate(&decode(&frame_img)).is_none());
+
+        // The content is neither close nor far enough, DSSIM rejects it
+        let detector = prefiltered(DEFAULT_THRESHOLD, 0, 64);
+        assert!(detector.find_slate(&decode(&slate_img)).is_some());
+        assert!(detector.find_slate(&decode(&frame_img)).is_none());
+    }
+
+    #[test]
+    fn compare_diff_images_with_excluded_regions() {
+        let (mut slate, file) = slate("../resources/slate_120px.jpg", "slate", None);
+        // Ignores the whole frame, so anything matches the slate
+        slate.regions = Some(vec![Region {
+            x: 0,
+            y: 0,
+            width: 1000,
+            height: 1000,
+            mode: RegionMode::Exclude,
+        }]);
+        let detector =
+            SlateDetector::new(vec![(slate, file)], &DetectorSettings::default()).unwrap();
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+
+        assert!(detector.find_slate(&decode(&frame_img)).is_some());
+    }
+
+    #[test]
+    fn mask_includes_and_excludes_regions() {
+        let region = |x, y, width, height, mode| Region {
+            x,
+            y,
+            width,
+            height,
+            mode,
+        };
+        let mask = Mask::new(
+            &[
+                region(0, 0, 3, 2, RegionMode::Include),
+                region(1, 0, 1, 1, RegionMode::Exclude),
+                // Clipped to the frame
+                region(3, 2, 10, 10, RegionMode::Include),
+            ],
+            4,
+            3,
+        );
+        #[rustfmt::skip]
+        let expected = vec![
+            true, false, true, false,
+            true, true, true, false,
+            false, false, false, true,
+        ];
+        assert_eq!(mask.included, expected);
+
+        let mask = Mask::new(&[region(0, 1, 4, 1, RegionMode::Exclude)], 4, 2);
+        assert_eq!(
+            mask.included,
+            vec![true, true, true, true, false, false, false, false]
+        );
+
+        // Regions reaching past the largest coordinate are clipped too
+        let mask = Mask::new(
+            &[region(u32::MAX, 1, 1, u32::MAX, RegionMode::Exclude)],
+            2,
+            2,
+        );
+        assert_eq!(mask.included, vec![true, true, true, true]);
+        let mask = Mask::new(
+            &[region(1, 1, u32::MAX, u32::MAX, RegionMode::Exclude)],
+            2,
+            2,
+        );
+        assert_eq!(mask.included, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn black_frame_needs_enough_dark_pixels() {
+        let mut detector = BlackFrameDetector::new(&BlackFrameDetection::default());
+        assert_eq!(
+            detector.detect(&solid_frame(0.002, 10, 10)),
+            Some((VideoMode::Black, None))
+        );
+        assert_eq!(detector.detect(&solid_frame(0.5, 10, 10)), None);
+
+        // 3 bright pixels out of 100 are too many
+        let mut frame = solid_frame(0.0, 10, 10);
+        for px in frame.bitmap.pixels.iter_mut().take(3) {
+            px.g = 1.0;
+        }
+        assert_eq!(detector.detect(&frame), None);
+        let slate = read_bytes("../resources/slate_120px.jpg");
+        assert_eq!(detector.detect(&decode(&slate)), None);
+    }
+
+    #[test]
+    fn frozen_frame_after_duration_without_changes() {
+        let mut detector = FrozenFrameDetector::new(&FrozenFrameDetection {
+            duration: Some(5),
+            max_difference: None,
+        });
+        assert_eq!(detector.detect(&solid_frame(0.5, 4, 4)), None);
+        FakeClock::advance_time(4000);
+        // Changes below the maximum difference don't end the freeze
+        assert_eq!(detector.detect(&solid_frame(0.505, 4, 4)), None);
+        FakeClock::advance_time(1000);
+        assert_eq!(
+            detector.detect(&solid_frame(0.5, 4, 4)),
+            Some((VideoMode::Frozen, None))
+        );
+
+        // A changing frame starts over
+        assert_eq!(detector.detect(&solid_frame(0.6, 4, 4)), None);
+        FakeClock::advance_time(1000);
+        assert_eq!(detector.detect(&solid_frame(0.6, 4, 4)), None);
+    }
+
+    #[test]
+    fn detectors_run_in_order() {
+        let settings = DetectorSettings {
+            black_frame: Some(BlackFrameDetection::default()),
+            frozen_frame: Some(FrozenFrameDetection {
+                duration: Some(1),
+                max_difference: None,
+            }),
+            ..Default::default()
+        };
+        let mut detectors = Detectors::new(
+            vec![slate("../resources/slate_120px.jpg", "ad-break", None)],
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(detectors.required_image_size(), (213, 120));
+
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        assert_eq!(
+            detectors.detect(&slate_img).unwrap(),
+            (VideoMode::Slate, Some("ad-break".to_string()))
+        );
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+        assert_eq!(
+            detectors.detect(&frame_img).unwrap(),
+            (VideoMode::Content, None)
+        );
+        FakeClock::advance_time(1000);
+        assert_eq!(
+            detectors.detect(&frame_img).unwrap(),
+            (VideoMode::Frozen, None)
+        );
+    }
+
+    #[test]
+    fn detectors_without_slates() {
+        let settings = DetectorSettings {
+            black_frame: Some(BlackFrameDetection::default()),
+            ..Default::default()
+        };
+        let detectors = Detectors::new(Vec::<(Slate, File)>::new(), &settings).unwrap();
+        assert_eq!(detectors.required_image_size(), DEFAULT_RESOLUTION);
+
+        assert!(Detectors::new(Vec::<(Slate, File)>::new(), &DetectorSettings::default()).is_err());
     }
 }
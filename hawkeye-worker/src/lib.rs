@@ -0,0 +1,22 @@
+//! Finds the video modes of a stream, e.g. slates, and runs the actions of their transitions.
+//! The worker binary runs a watcher with them.
+
+pub mod actions;
+mod assertion;
+pub mod audio;
+mod auth;
+mod aws;
+pub mod cue;
+pub mod debounce;
+pub mod img_detector;
+mod medialive;
+pub mod metrics;
+mod phash;
+pub mod queue;
+mod retry;
+pub mod rules;
+mod scte35;
+pub mod signal;
+mod template;
+pub mod ts;
+pub mod video_stream;
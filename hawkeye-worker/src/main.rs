@@ -1,26 +1,32 @@
-mod actions;
 mod config;
-mod img_detector;
-mod metrics;
-mod video_stream;
 
-use crate::actions::{ActionExecutor, Executors};
 use crate::config::AppConfig;
-use crate::img_detector::SlateDetector;
-use crate::metrics::run_metrics_service;
-use crate::video_stream::{create_pipeline, main_loop};
 use color_eyre::Result;
 use gstreamer as gst;
 use hawkeye_core::models::Watcher;
+use hawkeye_worker::actions::{self, ActionExecutor, Executors};
+use hawkeye_worker::audio::AudioDetector;
+use hawkeye_worker::cue::CueAgreement;
+use hawkeye_worker::debounce::ModeDebouncer;
+use hawkeye_worker::img_detector::Detectors;
+use hawkeye_worker::metrics::run_metrics_service;
+use hawkeye_worker::queue::Queue;
+use hawkeye_worker::rules::ModeRules;
+use hawkeye_worker::signal::{self, SignalMonitor};
+use hawkeye_worker::ts::Scte35Extractor;
+use hawkeye_worker::video_stream::{create_pipeline, main_loop};
 use log::info;
 use pretty_env_logger::env_logger;
 use std::fs::File;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::channel;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use structopt::StructOpt;
 
+/// Video modes waiting for the actions runtime, the oldest are dropped when it falls behind.
+const EVENT_QUEUE_CAPACITY: usize = 64;
+
 fn main() -> Result<()> {
     color_eyre::install()?;
     env_logger::init_from_env(
@@ -29,7 +35,9 @@ fn main() -> Result<()> {
 
     let config: AppConfig = AppConfig::from_args();
     let watcher_config = File::open(config.watcher_path)?;
-    let watcher: Watcher = serde_json::from_reader(watcher_config)?;
+    let mut watcher: Watcher = serde_json::from_reader(watcher_config)?;
+    // Validated once resolved, so the secrets are checked too
+    watcher.resolve_secrets(&config.secrets_path)?;
     watcher
         .is_valid()
         .expect("Invalid configuration for Watcher");
@@ -37,7 +45,7 @@ fn main() -> Result<()> {
     info!("Initializing GStreamer..");
     gst::init().expect("Could not initialize GStreamer!");
 
-    let (sender, receiver) = channel();
+    let events = Queue::bounded(EVENT_QUEUE_CAPACITY);
 
     info!("Loading executors..");
     let mut executors: Vec<ActionExecutor> = Vec::new();
@@ -46,8 +54,19 @@ fn main() -> Result<()> {
         executors.append(&mut execs.0);
     }
 
+    let runtime_events = events.clone();
+    let watcher_id = watcher.id.clone().unwrap_or_default();
+    let rules = ModeRules::new(watcher.rules.clone().unwrap_or_default());
+    let cue_check = watcher
+        .scte35
+        .map(|scte35| CueAgreement::new(scte35.disagreement_timeout));
     thread::spawn(move || {
-        let mut runtime = actions::Runtime::new(receiver, executors);
+        let mut runtime = actions::Runtime::new(runtime_events, executors)
+            .with_watcher_id(watcher_id)
+            .with_rules(rules);
+        if let Some(cue_check) = cue_check {
+            runtime = runtime.with_cue_check(cue_check);
+        }
 
         info!("Starting actions runtime..");
         runtime
@@ -66,19 +85,43 @@ fn main() -> Result<()> {
     })
     .expect("Error setting termination handler");
 
-    log::info!(
-        "Starting pipeline at rtp://0.0.0.0:{}",
-        watcher.source.ingest_port
-    );
-    let detector = SlateDetector::new(&mut watcher.slate()?)?;
+    match watcher.source.listen_port() {
+        Some(port) => log::info!(
+            "Starting pipeline at {}://0.0.0.0:{}",
+            watcher.source.transport.name(),
+            port
+        ),
+        None => log::info!("Starting {} pipeline", watcher.source.transport.name()),
+    }
+    let detector_settings = watcher.detector.clone().unwrap_or_default();
+    let mut slates = Vec::new();
+    for slate in watcher.reference_slates() {
+        let reader = slate.load()?;
+        slates.push((slate, reader));
+    }
+    let detectors = Detectors::new(slates, &detector_settings)?;
+    let debouncer = ModeDebouncer::new(&watcher.debounce.unwrap_or_default());
+    let signal = watcher.source.no_signal_timeout.map(|timeout| {
+        Arc::new(Mutex::new(SignalMonitor::new(Duration::from_millis(
+            timeout as u64,
+        ))))
+    });
+    if let Some(monitor) = signal.clone() {
+        let (events, running) = (events.clone(), running.clone());
+        thread::spawn(move || signal::watch(monitor, events, running));
+    }
+    let audio = watcher.audio.as_ref().map(AudioDetector::new);
+    let scte35 = watcher.scte35.map(|_| Scte35Extractor::default());
     create_pipeline(
-        detector,
-        watcher.source.ingest_port,
-        watcher.source.container,
-        watcher.source.codec,
-        sender.clone(),
+        detectors,
+        debouncer,
+        signal,
+        audio,
+        scte35,
+        &watcher.source,
+        events.clone(),
     )
-    .and_then(|pipeline| main_loop(pipeline, running, sender))?;
+    .and_then(|pipeline| main_loop(pipeline, running, events))?;
 
     Ok(())
 }
@@ -0,0 +1,211 @@
+//! Schedules SCTE-35 messages in AWS Elemental MediaLive channels.
+//!
+//! See: https://docs.aws.amazon.com/medialive/latest/apireference/channels-channelid-schedule.html
+
+use crate::aws::{self, Credentials};
+use crate::scte35::TICKS_PER_SECOND;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use hawkeye_core::models::{MediaLiveSchedule, SpliceKind};
+use lazy_static::lazy_static;
+use log::debug;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const SERVICE: &str = "medialive";
+
+lazy_static! {
+    /// Splice event id of the last splice insert scheduled in each channel, so the return to
+    /// network ends the same event.
+    static ref SPLICE_EVENT_IDS: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+}
+
+/// Body of the `BatchUpdateSchedule` request inserting the SCTE-35 message at the time of the
+/// detection, splice inserts use the event id as splice event id and last `duration` seconds when
+/// set. It is built once per execution and sent by every attempt, so retrying a request that
+/// MediaLive applied fails on the existing action name instead of inserting the message twice.
+pub fn schedule_request(
+    action: &MediaLiveSchedule,
+    duration: Option<u32>,
+    event_id: u32,
+    time: DateTime<Utc>,
+) -> String {
+    request_body(action, duration, splice_event_id(action, event_id), time).to_string()
+}
+
+/// Calls `BatchUpdateSchedule` with the body to immediately insert the SCTE-35 message in the
+/// channel, signed at the given time. The request is cancelled when it takes longer than the
+/// timeout, the response is returned failed or not.
+pub fn schedule(
+    action: &MediaLiveSchedule,
+    body: &str,
+    credentials: &Credentials,
+    time: DateTime<Utc>,
+    timeout: Duration,
+) -> Result<ureq::Response> {
+    let url = schedule_url(action);
+    let headers = [("content-type", "application/json")];
+    let signed_headers = aws::sign(
+        &aws::Request {
+            method: "PUT",
+            url: url.as_str(),
+            headers: &headers,
+            body: body.as_bytes(),
+        },
+        credentials,
+        &action.region,
+        SERVICE,
+        time,
+    )?;
+
+    let mut request = ureq::put(url.as_str());
+    request.timeout_connect(500);
+    request.timeout(match action.timeout {
+        Some(secs) => timeout.min(Duration::from_secs(secs as u64)),
+        None => timeout,
+    });
+    for (k, v) in headers.iter() {
+        request.set(k, v);
+    }
+    for (k, v) in signed_headers.iter() {
+        request.set(k, v);
+    }
+
+    debug!("Scheduling in MediaLive: {}", body);
+    Ok(request.send_string(body))
+}
+
+fn schedule_url(action: &MediaLiveSchedule) -> String {
+    let endpoint = match action.endpoint.as_ref() {
+        Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+        None => format!("https://medialive.{}.amazonaws.com", action.region),
+    };
+    format!("{}/prod/channels/{}/schedule", endpoint, action.channel_id)
+}
+
+/// Splice inserts start a new event, a return to network reuses the event of the channel.
+fn splice_event_id(action: &MediaLiveSchedule, event_id: u32) -> u32 {
+    let mut ids = SPLICE_EVENT_IDS.lock().unwrap();
+    match action.splice {
+        SpliceKind::SpliceInsert => {
+            ids.insert(action.channel_id.clone(), event_id);
+            event_id
+        }
+        SpliceKind::ReturnToNetwork => *ids.get(&action.channel_id).unwrap_or(&event_id),
+    }
+}
+
+fn request_body(
+    action: &MediaLiveSchedule,
+    duration: Option<u32>,
+    splice_event_id: u32,
+    time: DateTime<Utc>,
+) -> Value {
+    let (name, settings) = match action.splice {
+        SpliceKind::SpliceInsert => {
+            let mut settings = json!({ "SpliceEventId": splice_event_id });
+            if let Some(duration) = duration {
+                settings["Duration"] = json!(duration as u64 * TICKS_PER_SECOND);
+            }
+            (
+                "splice-insert",
+                json!({ "Scte35SpliceInsertSettings": settings }),
+            )
+        }
+        SpliceKind::ReturnToNetwork => (
+            "return-to-network",
+            json!({
+                "Scte35ReturnToNetworkSettings": { "SpliceEventId": splice_event_id }
+            }),
+        ),
+    };
+    json!({
+        "Creates": {
+            "ScheduleActions": [{
+                "ActionName": format!("hawkeye-{}-{}", name, time.timestamp_millis()),
+                "ScheduleActionStartSettings": {
+                    "ImmediateModeScheduleActionStartSettings": {}
+                },
+                "ScheduleActionSettings": settings
+            }]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(channel_id: &str, splice: SpliceKind) -> MediaLiveSchedule {
+        MediaLiveSchedule {
+            description: None,
+            region: "us-east-1".to_string(),
+            channel_id: channel_id.to_string(),
+            splice,
+            duration: None,
+            endpoint: None,
+            retries: None,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            cooldown: None,
+            max_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn splice_insert_request_body() {
+        let time = "2020-10-01T10:00:00Z".parse().unwrap();
+        let action = action("1234567", SpliceKind::SpliceInsert);
+
+        assert_eq!(
+            schedule_url(&action),
+            "https://medialive.us-east-1.amazonaws.com/prod/channels/1234567/schedule"
+        );
+        assert_eq!(
+            request_body(&action, Some(30), 42, time),
+            json!({
+                "Creates": {
+                    "ScheduleActions": [{
+                        "ActionName": "hawkeye-splice-insert-1601546400000",
+                        "ScheduleActionStartSettings": {
+                            "ImmediateModeScheduleActionStartSettings": {}
+                        },
+                        "ScheduleActionSettings": {
+                            "Scte35SpliceInsertSettings": {
+                                "SpliceEventId": 42,
+                                "Duration": 2700000
+                            }
+                        }
+                    }]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn return_to_network_request_body() {
+        let time = "2020-10-01T10:00:00Z".parse().unwrap();
+        let action = action("1234567", SpliceKind::ReturnToNetwork);
+
+        assert_eq!(
+            request_body(&action, None, 42, time)["Creates"]["ScheduleActions"][0]
+                ["ScheduleActionSettings"],
+            json!({
+                "Scte35ReturnToNetworkSettings": { "SpliceEventId": 42 }
+            })
+        );
+    }
+
+    #[test]
+    fn return_to_network_reuses_splice_event_id() {
+        let insert = action("splice-event-ids", SpliceKind::SpliceInsert);
+        let back = action("splice-event-ids", SpliceKind::ReturnToNetwork);
+
+        assert_eq!(splice_event_id(&insert, 10), 10);
+        assert_eq!(splice_event_id(&back, 11), 10);
+        assert_eq!(splice_event_id(&insert, 12), 12);
+    }
+}
@@ -2,7 +2,10 @@ use crate::video_stream;
 use lazy_static::lazy_static;
 use log::debug;
 use prometheus::{self, Encoder, TextEncoder};
-use prometheus::{register_histogram, register_int_counter, Histogram, IntCounter};
+use prometheus::{
+    register_gauge, register_histogram, register_int_counter, register_int_counter_vec, Gauge,
+    Histogram, IntCounter, IntCounterVec,
+};
 use tokio::runtime::Builder;
 use warp::hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
 use warp::hyper::{Body, StatusCode};
@@ -15,16 +18,93 @@ lazy_static! {
         "Number of times a slate image was found in the stream"
     )
     .unwrap();
+    pub static ref FOUND_SLATE_BY_LABEL_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "slate_label_found_in_stream",
+        "Number of times each labeled slate image was found in the stream",
+        &["slate"]
+    )
+    .unwrap();
+    pub static ref FOUND_BLACK_COUNTER: IntCounter = register_int_counter!(
+        "black_found_in_stream",
+        "Number of times a black frame was found in the stream"
+    )
+    .unwrap();
+    pub static ref FOUND_FROZEN_COUNTER: IntCounter = register_int_counter!(
+        "frozen_found_in_stream",
+        "Number of times a frame was found frozen in the stream"
+    )
+    .unwrap();
     pub static ref FOUND_CONTENT_COUNTER: IntCounter = register_int_counter!(
         "content_found_in_stream",
         "Number of times the content was found in the stream"
     )
     .unwrap();
+    pub static ref FOUND_SILENCE_COUNTER: IntCounter = register_int_counter!(
+        "silence_found_in_stream",
+        "Number of windows of audio found silent in the stream"
+    )
+    .unwrap();
+    pub static ref AUDIO_RMS_LEVEL: Gauge = register_gauge!(
+        "audio_rms_level_dbfs",
+        "Root mean square level of the last window of audio, in dBFS"
+    )
+    .unwrap();
+    pub static ref AUDIO_LOUDNESS: Gauge = register_gauge!(
+        "audio_loudness_lufs",
+        "Momentary loudness of the last window of audio, in LUFS"
+    )
+    .unwrap();
+    pub static ref FOUND_CUE_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "scte35_cue_found_in_stream",
+        "Number of SCTE-35 cues found in the stream by the mode they signal",
+        &["cue"]
+    )
+    .unwrap();
+    pub static ref SCTE35_DISAGREEMENT_COUNTER: IntCounter = register_int_counter!(
+        "scte35_slate_disagreement",
+        "Number of times the SCTE-35 cues and the slate detection disagreed for longer than the timeout"
+    )
+    .unwrap();
     pub static ref SIMILARITY_EXECUTION_COUNTER: IntCounter = register_int_counter!(
         "similarity_execution",
         "Number of times we searched for slate in the stream"
     )
     .unwrap();
+    pub static ref PERCEPTUAL_HASH_DECIDED_COUNTER: IntCounter = register_int_counter!(
+        "perceptual_hash_decided",
+        "Number of frames matched with a slate, or told apart from all of them, by their perceptual hash without DSSIM"
+    )
+    .unwrap();
+    pub static ref NO_SIGNAL_COUNTER: IntCounter = register_int_counter!(
+        "no_signal",
+        "Number of times the frames stopped arriving for longer than the no signal timeout"
+    )
+    .unwrap();
+    pub static ref MODE_CHANGE_SUPPRESSED_COUNTER: IntCounter = register_int_counter!(
+        "mode_change_suppressed",
+        "Number of times a video mode change was ignored for not being detected long enough"
+    )
+    .unwrap();
+    pub static ref ACTION_RATE_LIMITED_COUNTER: IntCounter = register_int_counter!(
+        "action_rate_limited",
+        "Number of times an action was not executed due to its cooldown or hourly limit"
+    )
+    .unwrap();
+    pub static ref ACTION_FAILED_COUNTER: IntCounter = register_int_counter!(
+        "action_execution_failed",
+        "Number of times an action execution failed"
+    )
+    .unwrap();
+    pub static ref ACTION_TIMED_OUT_COUNTER: IntCounter = register_int_counter!(
+        "action_execution_timed_out",
+        "Number of times an action execution was cancelled because it took too long"
+    )
+    .unwrap();
+    pub static ref QUEUE_DROPPED_COUNTER: IntCounter = register_int_counter!(
+        "queue_dropped",
+        "Number of events dropped because the queue of the actions was full"
+    )
+    .unwrap();
     pub static ref HTTP_CALL_DURATION: Histogram = register_histogram!(
         "http_call_action_execution_seconds",
         "Seconds it took to execute the HTTP call"
@@ -37,7 +117,7 @@ lazy_static! {
     .unwrap();
     pub static ref HTTP_CALL_ERROR_COUNTER: IntCounter = register_int_counter!(
         "http_call_error",
-        "Number of times the HTTP call returned an HTTP error status code"
+        "Number of times the HTTP call failed or returned an unexpected response"
     )
     .unwrap();
     pub static ref HTTP_CALL_RETRIED_COUNT: IntCounter = register_int_counter!(
@@ -45,11 +125,117 @@ lazy_static! {
         "Number of times the HTTP call was retried"
     )
     .unwrap();
+    pub static ref HTTP_CALL_ATTEMPT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "http_call_attempts",
+        "Number of HTTP call attempts by attempt number and result",
+        &["attempt", "result"]
+    )
+    .unwrap();
+    pub static ref HTTP_CALL_RETRY_DELAY: Histogram = register_histogram!(
+        "http_call_retry_delay_seconds",
+        "Seconds waited before retrying the HTTP call"
+    )
+    .unwrap();
     pub static ref HTTP_CALL_RETRIES_EXHAUSTED_COUNT: IntCounter = register_int_counter!(
         "http_call_retries_exhausted",
         "Number of times the HTTP action has exhausted all the retries"
     )
     .unwrap();
+    pub static ref EXEC_DURATION: Histogram = register_histogram!(
+        "exec_action_execution_seconds",
+        "Seconds it took to run the program"
+    )
+    .unwrap();
+    pub static ref EXEC_SUCCESS_COUNTER: IntCounter = register_int_counter!(
+        "exec_success",
+        "Number of times the program exited successfully"
+    )
+    .unwrap();
+    pub static ref EXEC_ERROR_COUNTER: IntCounter = register_int_counter!(
+        "exec_error",
+        "Number of times the program failed to run, timed out or exited with an unexpected code"
+    )
+    .unwrap();
+    pub static ref EXEC_RETRIED_COUNT: IntCounter =
+        register_int_counter!("exec_retried", "Number of times the program was run again").unwrap();
+    pub static ref EXEC_ATTEMPT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "exec_attempts",
+        "Number of program runs by attempt number and result",
+        &["attempt", "result"]
+    )
+    .unwrap();
+    pub static ref EXEC_RETRY_DELAY: Histogram = register_histogram!(
+        "exec_retry_delay_seconds",
+        "Seconds waited before running the program again"
+    )
+    .unwrap();
+    pub static ref EXEC_RETRIES_EXHAUSTED_COUNT: IntCounter = register_int_counter!(
+        "exec_retries_exhausted",
+        "Number of times the exec action has exhausted all the retries"
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_SUCCESS_COUNTER: IntCounter = register_int_counter!(
+        "medialive_schedule_success",
+        "Number of times a SCTE-35 message was scheduled in MediaLive"
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_ERROR_COUNTER: IntCounter = register_int_counter!(
+        "medialive_schedule_error",
+        "Number of times the MediaLive schedule update failed"
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_RETRIED_COUNT: IntCounter = register_int_counter!(
+        "medialive_schedule_retried",
+        "Number of times a MediaLive schedule update was retried"
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_ATTEMPT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "medialive_schedule_attempts",
+        "Number of MediaLive schedule updates by attempt number and result",
+        &["attempt", "result"]
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_RETRY_DELAY: Histogram = register_histogram!(
+        "medialive_schedule_retry_delay_seconds",
+        "Seconds waited before retrying the MediaLive schedule update"
+    )
+    .unwrap();
+    pub static ref MEDIALIVE_SCHEDULE_RETRIES_EXHAUSTED_COUNT: IntCounter = register_int_counter!(
+        "medialive_schedule_retries_exhausted",
+        "Number of times the MediaLive schedule action has exhausted all the retries"
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_SUCCESS_COUNTER: IntCounter = register_int_counter!(
+        "scte35_cue_success",
+        "Number of times a SCTE-35 cue was sent"
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_ERROR_COUNTER: IntCounter = register_int_counter!(
+        "scte35_cue_error",
+        "Number of times sending a SCTE-35 cue failed"
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_RETRIED_COUNT: IntCounter = register_int_counter!(
+        "scte35_cue_retried",
+        "Number of times a SCTE-35 cue was sent again"
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_ATTEMPT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "scte35_cue_attempts",
+        "Number of SCTE-35 cue sends by attempt number and result",
+        &["attempt", "result"]
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_RETRY_DELAY: Histogram = register_histogram!(
+        "scte35_cue_retry_delay_seconds",
+        "Seconds waited before sending the SCTE-35 cue again"
+    )
+    .unwrap();
+    pub static ref SCTE35_CUE_RETRIES_EXHAUSTED_COUNT: IntCounter = register_int_counter!(
+        "scte35_cue_retries_exhausted",
+        "Number of times the SCTE-35 cue action has exhausted all the retries"
+    )
+    .unwrap();
 }
 
 fn get_metric_contents() -> String {
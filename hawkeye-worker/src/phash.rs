@@ -0,0 +1,169 @@
+//! Perceptual hashes of images: 64 bits that barely change when the image barely changes, so
+//! similar images are found by counting the bits their hashes differ in.
+//!
+//! The hashes are computed from the luminance of the pixels and don't depend on the image size.
+
+use hawkeye_core::models::HashAlgorithm;
+use std::f32::consts::PI;
+
+/// Size of the image the discrete cosine transform runs on, of which the lowest 8x8 frequencies
+/// make the hash.
+const DCT_SIZE: usize = 32;
+
+/// Hashes the luminance of the pixels of an image of the given size, row by row.
+pub fn hash(algorithm: HashAlgorithm, luma: &[f32], width: usize, height: usize) -> u64 {
+    match algorithm {
+        HashAlgorithm::Ahash => {
+            let blocks = downscale(luma, width, height, 8, 8);
+            let mean = blocks.iter().sum::<f32>() / blocks.len() as f32;
+            bits(blocks.iter().map(|block| *block > mean))
+        }
+        HashAlgorithm::Dhash => {
+            let blocks = downscale(luma, width, height, 9, 8);
+            bits(
+                blocks
+                    .chunks_exact(9)
+                    .flat_map(|row| row.windows(2).map(|pair| pair[0] < pair[1])),
+            )
+        }
+        HashAlgorithm::Phash => {
+            let blocks = downscale(luma, width, height, DCT_SIZE, DCT_SIZE);
+            let frequencies = low_frequencies(&blocks);
+            // The first coefficient is the mean, which would skew the median
+            let mut sorted = frequencies[1..].to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let median = sorted[sorted.len() / 2];
+            bits(frequencies.iter().map(|frequency| *frequency > median))
+        }
+    }
+}
+
+/// Number of bits the hashes differ in, from 0 for similar images to 64.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn bits(bits: impl Iterator<Item = bool>) -> u64 {
+    bits.fold(0, |hash, bit| (hash << 1) | bit as u64)
+}
+
+/// Mean luminance of the blocks the image is divided in, row by row. Every block covers at least
+/// one pixel, so images smaller than the blocks are stretched.
+fn downscale(luma: &[f32], width: usize, height: usize, cols: usize, rows: usize) -> Vec<f32> {
+    let mut blocks = Vec::with_capacity(cols * rows);
+    if width == 0 || height == 0 || luma.len() < width * height {
+        blocks.resize(cols * rows, 0.0);
+        return blocks;
+    }
+    let span = |index: usize, blocks: usize, size: usize| {
+        let start = index * size / blocks;
+        let end = ((index + 1) * size / blocks).max(start + 1);
+        start.min(size - 1)..end.min(size)
+    };
+    for row in 0..rows {
+        let ys = span(row, rows, height);
+        for col in 0..cols {
+            let xs = span(col, cols, width);
+            let mut sum = 0.0;
+            for y in ys.clone() {
+                sum += luma[y * width + xs.start..y * width + xs.end]
+                    .iter()
+                    .sum::<f32>();
+            }
+            blocks.push(sum / (ys.len() * xs.len()) as f32);
+        }
+    }
+    blocks
+}
+
+/// Lowest 8x8 frequencies of the two dimensional DCT-II of the blocks, without normalization.
+fn low_frequencies(blocks: &[f32]) -> Vec<f32> {
+    let cosines: Vec<f32> = (0..8)
+        .flat_map(|u| {
+            (0..DCT_SIZE)
+                .map(move |x| ((2 * x + 1) as f32 * u as f32 * PI / (2 * DCT_SIZE) as f32).cos())
+        })
+        .collect();
+    let cosine = |u: usize, x: usize| cosines[u * DCT_SIZE + x];
+
+    // Transforms the rows, then the columns of the result
+    let mut rows = vec![0.0; DCT_SIZE * 8];
+    for y in 0..DCT_SIZE {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..DCT_SIZE)
+                .map(|x| blocks[y * DCT_SIZE + x] * cosine(u, x))
+                .sum();
+        }
+    }
+    let mut frequencies = Vec::with_capacity(64);
+    for v in 0..8 {
+        for u in 0..8 {
+            frequencies.push((0..DCT_SIZE).map(|y| rows[y * 8 + u] * cosine(v, y)).sum());
+        }
+    }
+    frequencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [HashAlgorithm; 3] = [
+        HashAlgorithm::Ahash,
+        HashAlgorithm::Dhash,
+        HashAlgorithm::Phash,
+    ];
+
+    /// Image with a bright disc in the middle of a horizontal gradient.
+    fn image(width: usize, height: usize, brightness: f32) -> Vec<f32> {
+        let mut luma = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (dx, dy) = (
+                    x as f32 / width as f32 - 0.5,
+                    y as f32 / height as f32 - 0.5,
+                );
+                let value = if dx * dx + dy * dy < 0.04 {
+                    0.9
+                } else {
+                    0.6 * x as f32 / width as f32
+                };
+                luma.push(value * brightness);
+            }
+        }
+        luma
+    }
+
+    #[test]
+    fn similar_images_have_close_hashes() {
+        for algorithm in ALGORITHMS.iter().copied() {
+            let reference = hash(algorithm, &image(160, 90, 1.0), 160, 90);
+            assert_eq!(hash(algorithm, &image(160, 90, 1.0), 160, 90), reference);
+            // Scaled and slightly darker
+            let similar = hash(algorithm, &image(213, 120, 0.95), 213, 120);
+            assert!(distance(similar, reference) <= 4, "{:?}", algorithm);
+
+            let mut flipped = image(160, 90, 1.0);
+            flipped.reverse();
+            let different = hash(algorithm, &flipped, 160, 90);
+            assert!(distance(different, reference) >= 20, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn hashes_images_smaller_than_blocks() {
+        let luma = [0.0, 1.0, 1.0, 0.0];
+        assert_eq!(
+            hash(HashAlgorithm::Ahash, &luma, 2, 2),
+            0x0F0F_0F0F_F0F0_F0F0
+        );
+        assert_eq!(hash(HashAlgorithm::Dhash, &[], 0, 0), 0);
+    }
+
+    #[test]
+    fn distance_counts_different_bits() {
+        assert_eq!(distance(0, 0), 0);
+        assert_eq!(distance(0b1011, 0b0110), 3);
+        assert_eq!(distance(0, u64::MAX), 64);
+    }
+}
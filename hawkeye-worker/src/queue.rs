@@ -0,0 +1,149 @@
+//! Bounded queue feeding the actions. Pushing never blocks, so a slow action can't hold back the
+//! video pipeline.
+
+use crate::metrics::QUEUE_DROPPED_COUNTER;
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+struct State<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    closed: bool,
+}
+
+/// Handle to a queue shared between threads, clones push to and pop from the same queue.
+pub struct Queue<T> {
+    shared: Arc<(Mutex<State<T>>, Condvar)>,
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> Queue<T> {
+    pub fn bounded(capacity: usize) -> Self {
+        Self::new(VecDeque::with_capacity(capacity), capacity)
+    }
+
+    /// Queue that never drops items, for items that are pushed only when they matter.
+    pub fn unbounded() -> Self {
+        Self::new(VecDeque::new(), usize::MAX)
+    }
+
+    fn new(items: VecDeque<T>, capacity: usize) -> Self {
+        let state = State {
+            items,
+            capacity,
+            closed: false,
+        };
+        Self {
+            shared: Arc::new((Mutex::new(state), Condvar::new())),
+        }
+    }
+
+    /// Adds the item, unless it is the same as the last item waiting. When the queue is full the
+    /// oldest item is dropped.
+    pub fn push(&self, item: T) {
+        let (state, available) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        if state.closed || state.items.back() == Some(&item) {
+            return;
+        }
+        if state.items.len() >= state.capacity {
+            warn!("Queue is full, dropping the oldest event");
+            QUEUE_DROPPED_COUNTER.inc();
+            state.items.pop_front();
+        }
+        state.items.push_back(item);
+        available.notify_one();
+    }
+
+    /// Waits for the next item. Returns `None` once the queue is closed and all the items were
+    /// taken.
+    pub fn pop(&self) -> Option<T> {
+        let (state, available) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                return Some(item);
+            }
+            if state.closed {
+                return None;
+            }
+            state = available.wait(state).unwrap();
+        }
+    }
+
+    /// Stops accepting items, the items already waiting can still be taken.
+    pub fn close(&self) {
+        let (state, available) = &*self.shared;
+        state.lock().unwrap().closed = true;
+        available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn coalesces_repeated_items() {
+        let queue = Queue::bounded(10);
+        queue.push(1);
+        queue.push(1);
+        queue.push(2);
+        queue.push(1);
+        queue.close();
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn drops_oldest_item_when_full() {
+        let queue = Queue::bounded(2);
+        queue.push(1);
+        queue.push(2);
+        queue.push(3);
+        queue.close();
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn unbounded_queue_keeps_every_item() {
+        let queue = Queue::unbounded();
+        for item in 0..100 {
+            queue.push(item);
+        }
+        queue.close();
+        assert_eq!((0..100).map(|_| queue.pop().unwrap()).sum::<i32>(), 4950);
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn pop_waits_for_items() {
+        let queue = Queue::bounded(2);
+        let consumer = queue.clone();
+        let handle = thread::spawn(move || {
+            let mut items = Vec::new();
+            while let Some(item) = consumer.pop() {
+                items.push(item);
+            }
+            items
+        });
+        queue.push(1);
+        queue.push(2);
+        queue.close();
+        queue.push(3);
+        assert_eq!(handle.join().unwrap(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use hawkeye_core::models::{RetryPolicy, RetryableError};
+use rand::Rng;
+use std::time::Duration;
+
+const DEFAULT_INITIAL_DELAY: u32 = 500;
+const DEFAULT_MULTIPLIER: u32 = 2;
+const DEFAULT_MAX_DELAY: u32 = 30_000;
+
+/// Exponential backoff between the retries of a call.
+pub struct Backoff {
+    next: Duration,
+    multiplier: u32,
+    max: Duration,
+    jitter: u32,
+}
+
+impl Backoff {
+    pub fn new(policy: &RetryPolicy) -> Self {
+        Self {
+            next: Duration::from_millis(
+                policy.initial_delay.unwrap_or(DEFAULT_INITIAL_DELAY) as u64
+            ),
+            multiplier: policy.multiplier.unwrap_or(DEFAULT_MULTIPLIER),
+            max: Duration::from_millis(policy.max_delay.unwrap_or(DEFAULT_MAX_DELAY) as u64),
+            jitter: policy.jitter.unwrap_or(0),
+        }
+    }
+
+    /// Delay before the next retry. A delay requested by the server takes precedence, but no
+    /// delay is longer than the maximum delay of the policy.
+    pub fn next_delay(&mut self, requested: Option<Duration>) -> Duration {
+        let delay = match requested {
+            Some(requested) => requested,
+            None => {
+                let jitter = if self.jitter > 0 {
+                    rand::thread_rng().gen_range(0..=self.jitter)
+                } else {
+                    0
+                };
+                self.next + Duration::from_millis(jitter as u64)
+            }
+        };
+        self.next = (self.next * self.multiplier).min(self.max);
+        delay.min(self.max)
+    }
+}
+
+/// Check if a response with this status code must be retried.
+pub fn is_retryable_status(policy: &RetryPolicy, status: u16) -> bool {
+    let listed = policy
+        .retryable_statuses
+        .as_ref()
+        .map_or(false, |statuses| statuses.contains(&status));
+    let kind = match status {
+        429 => Some(RetryableError::TooManyRequests),
+        500..=599 => Some(RetryableError::ServerError),
+        _ => None,
+    };
+    listed || kind.map_or(false, |kind| is_retryable_error(policy, kind))
+}
+
+/// Check if failures of this kind must be retried.
+pub fn is_retryable_error(policy: &RetryPolicy, kind: RetryableError) -> bool {
+    match policy.retryable_errors.as_ref() {
+        Some(errors) => errors.contains(&kind),
+        None => true,
+    }
+}
+
+/// Parses the `Retry-After` header, given in seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - now).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Some(100),
+            multiplier: Some(3),
+            max_delay: Some(1000),
+            jitter: None,
+            retryable_statuses: None,
+            retryable_errors: None,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_up_to_max_delay() {
+        let mut backoff = Backoff::new(&policy());
+        let delays: Vec<u128> = (0..5)
+            .map(|_| backoff.next_delay(None).as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 300, 900, 1000, 1000]);
+    }
+
+    #[test]
+    fn backoff_honors_requested_delay() {
+        let mut backoff = Backoff::new(&policy());
+        assert_eq!(
+            backoff.next_delay(Some(Duration::from_millis(700))),
+            Duration::from_millis(700)
+        );
+        assert_eq!(
+            backoff.next_delay(Some(Duration::from_secs(60))),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn backoff_adds_jitter() {
+        let mut policy = policy();
+        policy.jitter = Some(50);
+        let mut backoff = Backoff::new(&policy);
+        let delay = backoff.next_delay(None).as_millis();
+        assert!(delay >= 100 && delay <= 150);
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let mut policy = policy();
+        assert!(is_retryable_status(&policy, 503));
+        assert!(is_retryable_status(&policy, 429));
+        assert!(!is_retryable_status(&policy, 404));
+
+        policy.retryable_statuses = Some(vec![409]);
+        policy.retryable_errors = Some(vec![RetryableError::TooManyRequests]);
+        assert!(is_retryable_status(&policy, 409));
+        assert!(is_retryable_status(&policy, 429));
+        assert!(!is_retryable_status(&policy, 503));
+    }
+
+    #[test]
+    fn parse_retry_after_header() {
+        let now = "2015-10-21T07:28:00Z".parse().unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
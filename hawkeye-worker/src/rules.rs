@@ -0,0 +1,184 @@
+//! Evaluates the mode rules of the watcher, named modes entered when a combination of the modes
+//! detected in the video and in the audio holds for long enough.
+
+use hawkeye_core::models::{Condition, ModeKind, ModeRule, VideoMode};
+use log::debug;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// A rule, with when its condition started to hold.
+struct RuleState {
+    name: String,
+    condition: Condition,
+    duration: Duration,
+    holding_since: Option<Instant>,
+}
+
+/// Latest modes detected, the conditions of the rules are evaluated against them.
+#[derive(Default)]
+struct Detected {
+    modes: HashMap<ModeKind, VideoMode>,
+    slate_label: Option<String>,
+}
+
+impl Detected {
+    fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Mode(mode) => self.mode(mode.kind()) == *mode,
+            Condition::Slate(label) => {
+                self.mode(ModeKind::Video) == VideoMode::Slate
+                    && self.slate_label.as_ref() == Some(label)
+            }
+            Condition::All(conditions) => conditions.iter().all(|c| self.holds(c)),
+            Condition::Any(conditions) => conditions.iter().any(|c| self.holds(c)),
+            Condition::Not(condition) => !self.holds(condition),
+        }
+    }
+
+    fn mode(&self, kind: ModeKind) -> VideoMode {
+        self.modes.get(&kind).copied().unwrap_or(VideoMode::Unknown)
+    }
+}
+
+/// Finds the mode rule the watcher is in from the modes detected.
+pub struct ModeRules {
+    rules: Vec<RuleState>,
+    detected: Detected,
+    /// Index of the rule entered last.
+    current: Option<usize>,
+}
+
+impl ModeRules {
+    pub fn new(rules: Vec<ModeRule>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleState {
+                    name: rule.name,
+                    condition: rule.condition,
+                    duration: Duration::from_millis(rule.duration.unwrap_or(0) as u64),
+                    holding_since: None,
+                })
+                .collect(),
+            detected: Detected::default(),
+            current: None,
+        }
+    }
+
+    /// Records a mode detected in the video or in the audio, returning the name of the rule
+    /// entered when one is.
+    pub fn update(&mut self, mode: VideoMode, slate_label: Option<&str>) -> Option<String> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        if mode.kind() == ModeKind::Video {
+            self.detected.slate_label = slate_label.map(String::from);
+        }
+        self.detected.modes.insert(mode.kind(), mode);
+
+        // The first rule in order wins when several hold
+        let mut entered = None;
+        for (index, rule) in self.rules.iter_mut().enumerate() {
+            if !self.detected.holds(&rule.condition) {
+                rule.holding_since = None;
+                continue;
+            }
+            let since = rule.holding_since.get_or_insert_with(Instant::now);
+            if entered.is_none() && since.elapsed() >= rule.duration {
+                entered = Some(index);
+            }
+        }
+        match entered {
+            Some(index) if self.current != Some(index) => {
+                self.current = Some(index);
+                debug!("Entered mode rule {}", self.rules[index].name);
+                Some(self.rules[index].name.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_fake_clock::FakeClock;
+
+    fn rule(name: &str, condition: Condition, duration: Option<u32>) -> ModeRule {
+        ModeRule {
+            name: name.to_string(),
+            condition,
+            duration,
+        }
+    }
+
+    #[test]
+    fn enters_rule_after_condition_holds_for_duration() {
+        let mut rules = ModeRules::new(vec![rule(
+            "ad_break",
+            Condition::All(vec![
+                Condition::Slate("ad-break".to_string()),
+                Condition::Mode(VideoMode::Silence),
+            ]),
+            Some(2000),
+        )]);
+        assert_eq!(rules.update(VideoMode::Slate, Some("ad-break")), None);
+        assert_eq!(rules.update(VideoMode::Silence, None), None);
+        FakeClock::advance_time(1500);
+        // The condition stopped holding, the window starts again
+        assert_eq!(rules.update(VideoMode::Audio, None), None);
+        assert_eq!(rules.update(VideoMode::Silence, None), None);
+        FakeClock::advance_time(1999);
+        assert_eq!(rules.update(VideoMode::Slate, Some("ad-break")), None);
+        FakeClock::advance_time(1);
+        assert_eq!(
+            rules.update(VideoMode::Slate, Some("ad-break")),
+            Some("ad_break".to_string())
+        );
+        // Entered once
+        assert_eq!(rules.update(VideoMode::Silence, None), None);
+        assert_eq!(rules.update(VideoMode::Slate, Some("other")), None);
+    }
+
+    #[test]
+    fn stays_in_rule_until_another_is_entered() {
+        let mut rules = ModeRules::new(vec![
+            rule(
+                "outage",
+                Condition::Any(vec![
+                    Condition::Mode(VideoMode::Black),
+                    Condition::Mode(VideoMode::NoSignal),
+                ]),
+                None,
+            ),
+            rule(
+                "program",
+                Condition::Not(Box::new(Condition::Mode(VideoMode::Slate))),
+                None,
+            ),
+        ]);
+        assert_eq!(
+            rules.update(VideoMode::Content, None),
+            Some("program".to_string())
+        );
+        assert_eq!(rules.update(VideoMode::Slate, None), None);
+        assert_eq!(
+            rules.update(VideoMode::NoSignal, None),
+            Some("outage".to_string())
+        );
+        assert_eq!(
+            rules.update(VideoMode::Content, None),
+            Some("program".to_string())
+        );
+        // Both hold, the first one wins
+        assert_eq!(
+            rules.update(VideoMode::Black, None),
+            Some("outage".to_string())
+        );
+    }
+}
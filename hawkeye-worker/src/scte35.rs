@@ -0,0 +1,766 @@
+//! Encodes SCTE-35 splice information sections and sends them to splicers and encoders, and
+//! decodes the sections carried in the input.
+//!
+//! See: ANSI/SCTE 35, section 9 "Splice information section"
+
+use color_eyre::{eyre::eyre, Result};
+use hawkeye_core::models::{self, CueProtocol, Scte35Command, Scte35Cue, VideoMode};
+use log::{debug, info};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// SCTE-35 times and durations are expressed in ticks of a 90kHz clock.
+pub const TICKS_PER_SECOND: u64 = 90_000;
+
+const TABLE_ID: u8 = 0xFC;
+const SPLICE_NULL: u8 = 0x00;
+const SPLICE_INSERT: u8 = 0x05;
+const TIME_SIGNAL: u8 = 0x06;
+const SEGMENTATION_DESCRIPTOR: u8 = 0x02;
+const CUEI: u32 = 0x4355_4549;
+
+/// Splice information section with a single splice command, always in program splice mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpliceInfoSection {
+    pub pts_adjustment: u64,
+    pub cw_index: u8,
+    pub tier: u16,
+    pub command: SpliceCommand,
+    pub descriptors: Vec<SegmentationDescriptor>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpliceCommand {
+    /// Heartbeat without a splice. Cancelled splice inserts are also decoded as a splice null.
+    SpliceNull,
+    SpliceInsert(SpliceInsert),
+    /// Splice time in PTS ticks, immediate when not set.
+    TimeSignal(Option<u64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpliceInsert {
+    pub event_id: u32,
+    pub out_of_network: bool,
+    /// Splice time in PTS ticks, immediate when not set.
+    pub pts_time: Option<u64>,
+    pub break_duration: Option<BreakDuration>,
+    pub unique_program_id: u16,
+    pub avail_num: u8,
+    pub avails_expected: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakDuration {
+    pub auto_return: bool,
+    /// Duration in 90kHz ticks.
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentationDescriptor {
+    pub event_id: u32,
+    pub delivery_restrictions: Option<DeliveryRestrictions>,
+    /// Duration in 90kHz ticks.
+    pub duration: Option<u64>,
+    pub upid_type: u8,
+    pub upid: Vec<u8>,
+    pub type_id: u8,
+    pub segment_num: u8,
+    pub segments_expected: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryRestrictions {
+    pub web_delivery_allowed: bool,
+    pub no_regional_blackout: bool,
+    pub archive_allowed: bool,
+    pub device_restrictions: u8,
+}
+
+/// Writes fields of any bit length, most significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for bit in (0..bits).rev() {
+            let offset = self.bits % 8;
+            if offset == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> offset;
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.write(value as u64, 1);
+    }
+
+    /// Reserved bits are set to one.
+    fn reserved(&mut self, bits: u32) {
+        self.write(u64::MAX, bits);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write(*byte as u64, 8);
+        }
+    }
+}
+
+/// Reads fields of any bit length, most significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, bits: 0 }
+    }
+
+    fn read(&mut self, bits: u32) -> Result<u64> {
+        if self.bits + bits as usize > self.bytes.len() * 8 {
+            return Err(eyre!("SCTE-35 section is truncated"));
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.bytes[self.bits / 8];
+            value = (value << 1) | ((byte >> (7 - self.bits % 8)) & 1) as u64;
+            self.bits += 1;
+        }
+        Ok(value)
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        Ok(self.read(1)? == 1)
+    }
+
+    /// Reads whole bytes, the reader must be at the start of a byte.
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let start = self.bits / 8;
+        let bytes = self
+            .bytes
+            .get(start..start + len)
+            .ok_or_else(|| eyre!("SCTE-35 section is truncated"))?;
+        self.bits += len * 8;
+        Ok(bytes)
+    }
+
+    /// Moves to the byte at the position.
+    fn seek(&mut self, position: usize) -> Result<()> {
+        if position > self.bytes.len() {
+            return Err(eyre!("SCTE-35 section is truncated"));
+        }
+        self.bits = position * 8;
+        Ok(())
+    }
+}
+
+impl SpliceInfoSection {
+    /// Decodes a section, checking its CRC. Encrypted sections are not supported, and only the
+    /// segmentation descriptors are kept.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(bytes);
+        if reader.read(8)? != TABLE_ID as u64 {
+            return Err(eyre!("Not a SCTE-35 section"));
+        }
+        reader.read(4)?; // section_syntax_indicator, private_indicator and sap_type
+        let section_length = reader.read(12)? as usize;
+        let section = bytes
+            .get(..3 + section_length)
+            .ok_or_else(|| eyre!("SCTE-35 section is truncated"))?;
+        // The CRC of a section ending with its CRC is zero
+        if crc32_mpeg2(section) != 0 {
+            return Err(eyre!("SCTE-35 section has an invalid CRC"));
+        }
+        let mut reader = BitReader::new(section);
+        reader.seek(3)?;
+        reader.read(8)?; // protocol_version
+        if reader.flag()? {
+            return Err(eyre!("Encrypted SCTE-35 sections are not supported"));
+        }
+        reader.read(6)?; // encryption_algorithm
+        let pts_adjustment = reader.read(33)?;
+        let cw_index = reader.read(8)? as u8;
+        let tier = reader.read(12)? as u16;
+        let command_length = reader.read(12)? as usize;
+        let command_type = reader.read(8)? as u8;
+        let command_start = reader.bits / 8;
+        let command = match command_type {
+            SPLICE_NULL => SpliceCommand::SpliceNull,
+            SPLICE_INSERT => SpliceInsert::decode(&mut reader)?
+                .map_or(SpliceCommand::SpliceNull, SpliceCommand::SpliceInsert),
+            TIME_SIGNAL => SpliceCommand::TimeSignal(decode_splice_time(&mut reader)?),
+            _ => {
+                return Err(eyre!(
+                    "Unsupported splice command type {:#04x}",
+                    command_type
+                ))
+            }
+        };
+        // Legacy sections don't set the length of the command
+        if command_length != 0xFFF {
+            reader.seek(command_start + command_length)?;
+        }
+        let descriptor_loop_length = reader.read(16)? as usize;
+        let descriptors = decode_descriptors(reader.bytes(descriptor_loop_length)?)?;
+        Ok(Self {
+            pts_adjustment,
+            cw_index,
+            tier,
+            command,
+            descriptors,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut command = BitWriter::default();
+        let command_type = match &self.command {
+            SpliceCommand::SpliceNull => SPLICE_NULL,
+            SpliceCommand::SpliceInsert(insert) => {
+                insert.encode(&mut command);
+                SPLICE_INSERT
+            }
+            SpliceCommand::TimeSignal(pts_time) => {
+                splice_time(&mut command, *pts_time);
+                TIME_SIGNAL
+            }
+        };
+        let mut descriptors = BitWriter::default();
+        for descriptor in self.descriptors.iter() {
+            descriptor.encode(&mut descriptors);
+        }
+
+        // Everything after the section length, including the CRC
+        let section_length = 11 + command.bytes.len() + 2 + descriptors.bytes.len() + 4;
+        let mut section = BitWriter::default();
+        section.write(TABLE_ID as u64, 8);
+        section.flag(false); // section_syntax_indicator
+        section.flag(false); // private_indicator
+        section.write(0b11, 2); // sap_type, not specified
+        section.write(section_length as u64, 12);
+        section.write(0, 8); // protocol_version
+        section.flag(false); // encrypted_packet
+        section.write(0, 6); // encryption_algorithm
+        section.write(self.pts_adjustment, 33);
+        section.write(self.cw_index as u64, 8);
+        section.write(self.tier as u64, 12);
+        section.write(command.bytes.len() as u64, 12);
+        section.write(command_type as u64, 8);
+        section.bytes(&command.bytes);
+        section.write(descriptors.bytes.len() as u64, 16);
+        section.bytes(&descriptors.bytes);
+        let crc = crc32_mpeg2(&section.bytes);
+        section.write(crc as u64, 32);
+        section.bytes
+    }
+}
+
+impl SpliceInsert {
+    fn encode(&self, writer: &mut BitWriter) {
+        writer.write(self.event_id as u64, 32);
+        writer.flag(false); // splice_event_cancel_indicator
+        writer.reserved(7);
+        writer.flag(self.out_of_network);
+        writer.flag(true); // program_splice_flag
+        writer.flag(self.break_duration.is_some());
+        writer.flag(self.pts_time.is_none()); // splice_immediate_flag
+        writer.reserved(4);
+        if self.pts_time.is_some() {
+            splice_time(writer, self.pts_time);
+        }
+        if let Some(break_duration) = &self.break_duration {
+            writer.flag(break_duration.auto_return);
+            writer.reserved(6);
+            writer.write(break_duration.duration, 33);
+        }
+        writer.write(self.unique_program_id as u64, 16);
+        writer.write(self.avail_num as u64, 8);
+        writer.write(self.avails_expected as u64, 8);
+    }
+}
+
+impl SpliceInsert {
+    /// Decodes the splice insert, `None` when the event is cancelled. The splice times of the
+    /// components are skipped.
+    fn decode(reader: &mut BitReader) -> Result<Option<Self>> {
+        let event_id = reader.read(32)? as u32;
+        let cancelled = reader.flag()?;
+        reader.read(7)?;
+        if cancelled {
+            return Ok(None);
+        }
+        let out_of_network = reader.flag()?;
+        let program_splice = reader.flag()?;
+        let has_duration = reader.flag()?;
+        let immediate = reader.flag()?;
+        reader.read(4)?;
+        let mut pts_time = None;
+        if program_splice && !immediate {
+            pts_time = decode_splice_time(reader)?;
+        }
+        if !program_splice {
+            let components = reader.read(8)?;
+            for _ in 0..components {
+                reader.read(8)?; // component_tag
+                if !immediate {
+                    decode_splice_time(reader)?;
+                }
+            }
+        }
+        let break_duration = if has_duration {
+            let auto_return = reader.flag()?;
+            reader.read(6)?;
+            Some(BreakDuration {
+                auto_return,
+                duration: reader.read(33)?,
+            })
+        } else {
+            None
+        };
+        Ok(Some(Self {
+            event_id,
+            out_of_network,
+            pts_time,
+            break_duration,
+            unique_program_id: reader.read(16)? as u16,
+            avail_num: reader.read(8)? as u8,
+            avails_expected: reader.read(8)? as u8,
+        }))
+    }
+}
+
+fn decode_splice_time(reader: &mut BitReader) -> Result<Option<u64>> {
+    if reader.flag()? {
+        reader.read(6)?;
+        Ok(Some(reader.read(33)?))
+    } else {
+        reader.read(7)?;
+        Ok(None)
+    }
+}
+
+fn splice_time(writer: &mut BitWriter, pts_time: Option<u64>) {
+    match pts_time {
+        Some(pts_time) => {
+            writer.flag(true); // time_specified_flag
+            writer.reserved(6);
+            writer.write(pts_time, 33);
+        }
+        None => {
+            writer.flag(false);
+            writer.reserved(7);
+        }
+    }
+}
+
+impl SegmentationDescriptor {
+    fn encode(&self, writer: &mut BitWriter) {
+        let mut body = BitWriter::default();
+        body.write(CUEI as u64, 32);
+        body.write(self.event_id as u64, 32);
+        body.flag(false); // segmentation_event_cancel_indicator
+        body.reserved(7);
+        body.flag(true); // program_segmentation_flag
+        body.flag(self.duration.is_some());
+        match &self.delivery_restrictions {
+            Some(restrictions) => {
+                body.flag(false); // delivery_not_restricted_flag
+                body.flag(restrictions.web_delivery_allowed);
+                body.flag(restrictions.no_regional_blackout);
+                body.flag(restrictions.archive_allowed);
+                body.write(restrictions.device_restrictions as u64, 2);
+            }
+            None => {
+                body.flag(true);
+                body.reserved(5);
+            }
+        }
+        if let Some(duration) = self.duration {
+            body.write(duration, 40);
+        }
+        body.write(self.upid_type as u64, 8);
+        body.write(self.upid.len() as u64, 8);
+        body.bytes(&self.upid);
+        body.write(self.type_id as u64, 8);
+        body.write(self.segment_num as u64, 8);
+        body.write(self.segments_expected as u64, 8);
+
+        writer.write(SEGMENTATION_DESCRIPTOR as u64, 8);
+        writer.write(body.bytes.len() as u64, 8);
+        writer.bytes(&body.bytes);
+    }
+}
+
+/// Decodes the segmentation descriptors of the loop, skipping the other descriptors and the
+/// cancelled segmentation events.
+fn decode_descriptors(mut bytes: &[u8]) -> Result<Vec<SegmentationDescriptor>> {
+    let mut descriptors = Vec::new();
+    while bytes.len() >= 2 {
+        let (tag, len) = (bytes[0], bytes[1] as usize);
+        let body = bytes
+            .get(2..2 + len)
+            .ok_or_else(|| eyre!("SCTE-35 descriptor is truncated"))?;
+        if tag == SEGMENTATION_DESCRIPTOR {
+            if let Some(descriptor) = SegmentationDescriptor::decode(body)? {
+                descriptors.push(descriptor);
+            }
+        }
+        bytes = &bytes[2 + len..];
+    }
+    Ok(descriptors)
+}
+
+impl SegmentationDescriptor {
+    fn decode(body: &[u8]) -> Result<Option<Self>> {
+        let mut reader = BitReader::new(body);
+        if reader.read(32)? != CUEI as u64 {
+            return Ok(None);
+        }
+        let event_id = reader.read(32)? as u32;
+        let cancelled = reader.flag()?;
+        reader.read(7)?;
+        if cancelled {
+            return Ok(None);
+        }
+        let program_segmentation = reader.flag()?;
+        let has_duration = reader.flag()?;
+        let delivery_restrictions = if reader.flag()? {
+            reader.read(5)?;
+            None
+        } else {
+            Some(DeliveryRestrictions {
+                web_delivery_allowed: reader.flag()?,
+                no_regional_blackout: reader.flag()?,
+                archive_allowed: reader.flag()?,
+                device_restrictions: reader.read(2)? as u8,
+            })
+        };
+        if !program_segmentation {
+            let components = reader.read(8)?;
+            for _ in 0..components {
+                reader.read(8)?; // component_tag
+                reader.read(7)?;
+                reader.read(33)?; // pts_offset
+            }
+        }
+        let duration = if has_duration {
+            Some(reader.read(40)?)
+        } else {
+            None
+        };
+        let upid_type = reader.read(8)? as u8;
+        let upid_length = reader.read(8)? as usize;
+        let upid = reader.bytes(upid_length)?.to_vec();
+        Ok(Some(Self {
+            event_id,
+            delivery_restrictions,
+            duration,
+            upid_type,
+            upid,
+            type_id: reader.read(8)? as u8,
+            segment_num: reader.read(8)? as u8,
+            segments_expected: reader.read(8)? as u8,
+        }))
+    }
+}
+
+/// Mode signalled by the section: `cue_out` when the program leaves for a break, and `cue_in`
+/// when it returns. Other sections, like heartbeats, signal no mode.
+pub fn cue_mode(section: &SpliceInfoSection) -> Option<VideoMode> {
+    if let SpliceCommand::SpliceInsert(insert) = &section.command {
+        return Some(if insert.out_of_network {
+            VideoMode::CueOut
+        } else {
+            VideoMode::CueIn
+        });
+    }
+    section
+        .descriptors
+        .iter()
+        .find_map(|descriptor| match descriptor.type_id {
+            // Starts and ends of breaks, of advertisements and of placement opportunities
+            0x22 | 0x30 | 0x32 | 0x34 | 0x36 => Some(VideoMode::CueOut),
+            0x23 | 0x31 | 0x33 | 0x35 | 0x37 => Some(VideoMode::CueIn),
+            _ => None,
+        })
+}
+
+/// CRC-32 of MPEG-2 sections: polynomial 0x04C11DB7, no reflection and no final XOR.
+pub(crate) fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Builds the section of the cue. The event id of the detection is used when the cue doesn't
+/// set one.
+pub fn section(cue: &Scte35Cue, event_id: u32) -> Result<SpliceInfoSection> {
+    let event_id = cue.event_id.unwrap_or(event_id);
+    let command = match cue.command {
+        Scte35Command::SpliceInsert => SpliceCommand::SpliceInsert(SpliceInsert {
+            event_id,
+            out_of_network: cue.out_of_network.unwrap_or(true),
+            pts_time: None,
+            break_duration: cue.duration.map(|secs| BreakDuration {
+                auto_return: true,
+                duration: secs as u64 * TICKS_PER_SECOND,
+            }),
+            unique_program_id: cue.unique_program_id.unwrap_or(0),
+            avail_num: 0,
+            avails_expected: 0,
+        }),
+        Scte35Command::TimeSignal => SpliceCommand::TimeSignal(None),
+    };
+    let descriptors = cue
+        .segmentation_descriptors
+        .iter()
+        .flatten()
+        .map(|descriptor| segmentation_descriptor(descriptor, event_id))
+        .collect::<Result<_>>()?;
+    Ok(SpliceInfoSection {
+        pts_adjustment: 0,
+        cw_index: 0xFF,
+        tier: 0xFFF,
+        command,
+        descriptors,
+    })
+}
+
+fn segmentation_descriptor(
+    descriptor: &models::SegmentationDescriptor,
+    event_id: u32,
+) -> Result<SegmentationDescriptor> {
+    let upid = match &descriptor.upid {
+        Some(upid) => hex::decode(upid).map_err(|err| eyre!("Invalid UPID {}: {}", upid, err))?,
+        None => Vec::new(),
+    };
+    if upid.len() > u8::MAX as usize {
+        return Err(eyre!("UPID is longer than 255 bytes"));
+    }
+    Ok(SegmentationDescriptor {
+        event_id: descriptor.event_id.unwrap_or(event_id),
+        delivery_restrictions: None,
+        duration: descriptor
+            .duration
+            .map(|secs| secs as u64 * TICKS_PER_SECOND),
+        upid_type: descriptor.upid_type.unwrap_or(0),
+        upid,
+        type_id: descriptor.type_id,
+        segment_num: descriptor.segment_num.unwrap_or(0),
+        segments_expected: descriptor.segments_expected.unwrap_or(0),
+    })
+}
+
+/// Sends the section of the cue in a single UDP datagram, or over a TCP connection that is closed
+/// afterwards. Connecting and sending over TCP is cancelled when it takes longer than the timeout.
+pub fn send(cue: &Scte35Cue, event_id: u32, timeout: Duration) -> Result<()> {
+    let section = section(cue, event_id)?.encode();
+    debug!(
+        "Sending SCTE-35 section to {}: {}",
+        cue.address,
+        hex::encode(&section)
+    );
+    match cue.protocol {
+        CueProtocol::Udp => {
+            let socket = UdpSocket::bind("0.0.0.0:0")?;
+            socket.send_to(&section, cue.address.as_str())?;
+        }
+        CueProtocol::Tcp => {
+            let timeout = match cue.timeout {
+                Some(secs) => timeout.min(Duration::from_secs(secs as u64)),
+                None => timeout,
+            };
+            let address = cue
+                .address
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| eyre!("Address {} can't be resolved", cue.address))?;
+            let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+            stream.set_write_timeout(Some(timeout))?;
+            stream.write_all(&section)?;
+        }
+    }
+    info!("Sent SCTE-35 {:?} to {}", cue.command, cue.address);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_mpeg2_check_value() {
+        assert_eq!(crc32_mpeg2(b"123456789"), 0x0376_E6E7);
+    }
+
+    #[test]
+    fn encode_splice_insert() {
+        let section = SpliceInfoSection {
+            pts_adjustment: 0,
+            cw_index: 0,
+            tier: 0xFFF,
+            command: SpliceCommand::SpliceInsert(SpliceInsert {
+                event_id: 1,
+                out_of_network: true,
+                pts_time: Some(0x2D14_2B00),
+                break_duration: Some(BreakDuration {
+                    auto_return: true,
+                    duration: 0x0123_D308,
+                }),
+                unique_program_id: 1,
+                avail_num: 1,
+                avails_expected: 1,
+            }),
+            descriptors: Vec::new(),
+        };
+        assert_eq!(
+            hex::encode(section.encode()),
+            "fc302500000000000000fff01405000000017feffe2d142b00fe0123d3080001010100007f157a49"
+        );
+        assert_eq!(
+            SpliceInfoSection::decode(&section.encode()).unwrap(),
+            section
+        );
+        assert_eq!(cue_mode(&section), Some(VideoMode::CueOut));
+    }
+
+    #[test]
+    fn encode_time_signal_with_segmentation_descriptor() {
+        // Sample 14.1 of the SCTE-35 specification
+        let section = SpliceInfoSection {
+            pts_adjustment: 0,
+            cw_index: 0xFF,
+            tier: 0xFFF,
+            command: SpliceCommand::TimeSignal(Some(0x72BD_0050)),
+            descriptors: vec![SegmentationDescriptor {
+                event_id: 0x4800_008E,
+                delivery_restrictions: Some(DeliveryRestrictions {
+                    web_delivery_allowed: false,
+                    no_regional_blackout: true,
+                    archive_allowed: true,
+                    device_restrictions: 3,
+                }),
+                duration: Some(0x01A5_99B0),
+                upid_type: 0x08,
+                upid: vec![0x00, 0x00, 0x00, 0x00, 0x2C, 0xA0, 0xA1, 0x8A],
+                type_id: 0x34,
+                segment_num: 2,
+                segments_expected: 0,
+            }],
+        };
+        assert_eq!(
+            hex::encode(section.encode()),
+            "fc3034000000000000fffff00506fe72bd0050001e021c435545494800008e7fcf0001a599b00808000000002ca0a18a3402009ac9d17e"
+        );
+        assert_eq!(
+            SpliceInfoSection::decode(&section.encode()).unwrap(),
+            section
+        );
+        // Provider placement opportunity start
+        assert_eq!(cue_mode(&section), Some(VideoMode::CueOut));
+    }
+
+    #[test]
+    fn decode_rejects_invalid_sections() {
+        let mut section = SpliceInfoSection {
+            pts_adjustment: 0,
+            cw_index: 0xFF,
+            tier: 0xFFF,
+            command: SpliceCommand::SpliceNull,
+            descriptors: Vec::new(),
+        }
+        .encode();
+        assert!(SpliceInfoSection::decode(&section).is_ok());
+        assert_eq!(
+            cue_mode(&SpliceInfoSection::decode(&section).unwrap()),
+            None
+        );
+
+        let last = section.len() - 1;
+        section[last] ^= 1;
+        assert!(SpliceInfoSection::decode(&section).is_err());
+        assert!(SpliceInfoSection::decode(&section[..last]).is_err());
+        assert!(SpliceInfoSection::decode(&[0x00, 0x30]).is_err());
+    }
+
+    #[test]
+    fn decode_cancelled_splice_insert() {
+        // Splice insert of event 1 with the splice_event_cancel_indicator set
+        let mut body = vec![0x05, 0x00, 0x00, 0x00, 0x01, 0xFF];
+        let mut section = vec![
+            0xFC, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF,
+        ];
+        section.push(0xF0);
+        section.push(body.len() as u8 - 1);
+        section.append(&mut body);
+        section.extend_from_slice(&[0x00, 0x00]);
+        section[2] = (section.len() - 3 + 4) as u8;
+        let crc = crc32_mpeg2(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        assert_eq!(
+            SpliceInfoSection::decode(&section).unwrap().command,
+            SpliceCommand::SpliceNull
+        );
+    }
+
+    #[test]
+    fn section_of_immediate_splice_insert() {
+        let cue = Scte35Cue {
+            description: None,
+            protocol: CueProtocol::Udp,
+            address: "127.0.0.1:5000".to_string(),
+            command: Scte35Command::SpliceInsert,
+            event_id: None,
+            duration: Some(30),
+            out_of_network: None,
+            unique_program_id: None,
+            segmentation_descriptors: None,
+            retries: None,
+            timeout: None,
+            execution_timeout: None,
+            retry_policy: None,
+            cooldown: None,
+            max_per_hour: None,
+        };
+        let section = section(&cue, 7).unwrap();
+        assert_eq!(
+            section.command,
+            SpliceCommand::SpliceInsert(SpliceInsert {
+                event_id: 7,
+                out_of_network: true,
+                pts_time: None,
+                break_duration: Some(BreakDuration {
+                    auto_return: true,
+                    duration: 2_700_000,
+                }),
+                unique_program_id: 0,
+                avail_num: 0,
+                avails_expected: 0,
+            })
+        );
+        // Immediate splices have no splice time
+        assert_eq!(
+            hex::encode(section.encode()),
+            "fc3020000000000000fffff00f05000000077ffffe002932e0000000000000c5d69da6"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Detects when the frames stop arriving, so transitions into the `no_signal` mode can trigger
+//! failover or alerting actions.
+
+use crate::metrics::NO_SIGNAL_COUNTER;
+use crate::queue::Queue;
+use crate::video_stream::Event;
+use hawkeye_core::models::VideoMode;
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// Time between the checks of whether the frames stopped arriving.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tracks the arrival of the frames. The timeout starts when the watcher starts, so a feed that
+/// never arrives is also reported.
+pub struct SignalMonitor {
+    timeout: Duration,
+    last_frame: Instant,
+    lost: bool,
+}
+
+impl SignalMonitor {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_frame: Instant::now(),
+            lost: false,
+        }
+    }
+
+    /// Records the arrival of a frame, returns `true` when the signal was lost until now.
+    pub fn frame(&mut self) -> bool {
+        self.last_frame = Instant::now();
+        let restored = self.lost;
+        if restored {
+            info!("Frames arrive again, signal restored");
+        }
+        self.lost = false;
+        restored
+    }
+
+    /// Returns `true` only once when no frame arrived within the timeout, until frames arrive
+    /// again.
+    pub fn check(&mut self) -> bool {
+        if self.lost || self.last_frame.elapsed() < self.timeout {
+            return false;
+        }
+        warn!(
+            "No frames arrived in the last {:?}, signal lost",
+            self.timeout
+        );
+        self.lost = true;
+        true
+    }
+}
+
+/// Pushes the `no_signal` mode to the actions when the frames stop arriving, until the watcher
+/// stops running.
+pub fn watch(monitor: Arc<Mutex<SignalMonitor>>, events: Queue<Event>, running: Arc<AtomicBool>) {
+    while running.load(Ordering::SeqCst) {
+        thread::sleep(CHECK_INTERVAL);
+        if monitor.lock().unwrap().check() {
+            NO_SIGNAL_COUNTER.inc();
+            events.push(Event::Mode(VideoMode::NoSignal, None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_fake_clock::FakeClock;
+
+    #[test]
+    fn signal_lost_when_frames_stop_arriving() {
+        let mut monitor = SignalMonitor::new(Duration::from_secs(5));
+        FakeClock::advance_time(4000);
+        assert!(!monitor.check());
+        assert!(!monitor.frame());
+
+        FakeClock::advance_time(4999);
+        assert!(!monitor.check());
+        FakeClock::advance_time(1);
+        assert!(monitor.check());
+        // Reported once
+        FakeClock::advance_time(5000);
+        assert!(!monitor.check());
+
+        assert!(monitor.frame());
+        assert!(!monitor.frame());
+        assert!(!monitor.check());
+    }
+
+    #[test]
+    fn signal_lost_when_no_frame_ever_arrives() {
+        let mut monitor = SignalMonitor::new(Duration::from_millis(500));
+        FakeClock::advance_time(500);
+        assert!(monitor.check());
+    }
+}
@@ -0,0 +1,248 @@
+//! Templates evaluated when an action executes, variables are written as `{{ name }}`.
+//!
+//! Values are escaped for the destination of the rendered template, so a value can't change the
+//! structure of the JSON body or the URL it is inserted in.
+
+use crate::actions::ActionContext;
+use crate::assertion::json_value;
+use color_eyre::{eyre::eyre, Result};
+
+/// Variables with the context of the detection.
+pub const CONTEXT_VARIABLES: [&str; 10] = [
+    "watcher_id",
+    "event_id",
+    "mode",
+    "previous_mode",
+    "slate_label",
+    "previous_slate_label",
+    "timestamp",
+    "timestamp_ms",
+    "previous_mode_duration_ms",
+    "audio",
+];
+
+/// Escaping of the values inserted in a template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Escape {
+    /// Values are inserted as they are, in headers, program arguments and environment variables.
+    None,
+    /// Values are escaped as the content of a JSON string, in bodies with a JSON content type.
+    Json,
+    /// Values are percent-encoded, in URLs.
+    Url,
+}
+
+impl Escape {
+    fn apply(self, value: String) -> Result<String> {
+        match self {
+            Escape::None => Ok(value),
+            Escape::Json => {
+                let quoted = serde_json::to_string(&value)?;
+                Ok(quoted[1..quoted.len() - 1].to_string())
+            }
+            Escape::Url => Ok(percent_encode(&value)),
+        }
+    }
+}
+
+/// Encodes every byte of the value but the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Replaces the variables in the template with the values of the context, escaped for the
+/// destination of the template.
+pub fn render(template: &str, context: &ActionContext, escape: Escape) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| eyre!("Unclosed variable in template: {}", template))?;
+        let name = rest[start + 2..start + end].trim();
+        rendered.push_str(&escape.apply(value(name, context)?)?);
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Value of the variable in the context.
+pub fn value(name: &str, context: &ActionContext) -> Result<String> {
+    let value = match name {
+        "watcher_id" => context.watcher_id.clone(),
+        "event_id" => context.event_id.to_string(),
+        "mode" => context.mode.name().to_string(),
+        "previous_mode" => context.previous_mode.name().to_string(),
+        "slate_label" => context.slate_label.clone().unwrap_or_default(),
+        "previous_slate_label" => context.previous_slate_label.clone().unwrap_or_default(),
+        "timestamp" => context.timestamp.to_rfc3339(),
+        "timestamp_ms" => context.timestamp.timestamp_millis().to_string(),
+        "previous_mode_duration_ms" => context.previous_mode_duration.as_millis().to_string(),
+        "audio" => context
+            .audio
+            .map(|audio| audio.name().to_string())
+            .unwrap_or_default(),
+        _ if name.starts_with("steps.") => step_value(&name["steps.".len()..], context)?,
+        _ => return Err(eyre!("Unknown template variable: {}", name)),
+    };
+    Ok(value)
+}
+
+/// Value of the response of a previous step: `steps.{id}.status`, `steps.{id}.body`, or
+/// `steps.{id}.body.{path}` for the value at the path of a JSON body.
+fn step_value(name: &str, context: &ActionContext) -> Result<String> {
+    let (id, field) = match name.find('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let response = context
+        .steps
+        .get(id)
+        .ok_or_else(|| eyre!("No response of step {} in the template context", id))?;
+    match field {
+        "status" => Ok(response.status.to_string()),
+        "body" => Ok(response.body.clone()),
+        _ if field.starts_with("body.") || field.starts_with("body[") => {
+            json_value(&response.body, &format!("${}", &field["body".len()..]))
+        }
+        _ => Err(eyre!("Unknown template variable: steps.{}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Response;
+    use hawkeye_core::models::VideoMode;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn context() -> ActionContext {
+        ActionContext {
+            watcher_id: "ee21fc9a".to_string(),
+            event_id: 42,
+            mode: VideoMode::Content.into(),
+            slate_label: None,
+            previous_mode: VideoMode::Slate.into(),
+            previous_slate_label: Some("ad-break".to_string()),
+            timestamp: "2020-10-01T10:00:00Z".parse().unwrap(),
+            previous_mode_duration: Duration::from_millis(30500),
+            audio: Some(VideoMode::Silence),
+            steps: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn render_variables() {
+        assert_eq!(
+            render(
+                r#"{"id":{{event_id}},"watcher":"{{ watcher_id }}","slate":"{{previous_slate_label}}","mode":"{{previous_mode}}-{{mode}}","after":{{ previous_mode_duration_ms }}}"#,
+                &context(),
+                Escape::None
+            )
+            .unwrap(),
+            r#"{"id":42,"watcher":"ee21fc9a","slate":"ad-break","mode":"slate-content","after":30500}"#
+        );
+        assert_eq!(
+            render("{{timestamp}} {{timestamp_ms}}", &context(), Escape::None).unwrap(),
+            "2020-10-01T10:00:00+00:00 1601546400000"
+        );
+        assert_eq!(
+            render("{{ audio }}", &context(), Escape::None).unwrap(),
+            "silence"
+        );
+    }
+
+    #[test]
+    fn render_slate_labels_separately() {
+        let context = context();
+        assert_eq!(
+            render(
+                "{{ slate_label }}|{{ previous_slate_label }}",
+                &context,
+                Escape::None
+            )
+            .unwrap(),
+            "|ad-break"
+        );
+    }
+
+    #[test]
+    fn render_escapes_values_for_json() {
+        let mut context = context();
+        context.watcher_id = "a\"b\\c\n".to_string();
+        assert_eq!(
+            render(
+                r#"{"watcher":"{{ watcher_id }}","id":{{ event_id }}}"#,
+                &context,
+                Escape::Json
+            )
+            .unwrap(),
+            r#"{"watcher":"a\"b\\c\n","id":42}"#
+        );
+    }
+
+    #[test]
+    fn render_percent_encodes_values_for_urls() {
+        let mut context = context();
+        context.previous_slate_label = Some("ad break/1?x=é&y".to_string());
+        assert_eq!(
+            render(
+                "http://ads.example.com/breaks/{{ previous_slate_label }}?id={{ event_id }}",
+                &context,
+                Escape::Url
+            )
+            .unwrap(),
+            "http://ads.example.com/breaks/ad%20break%2F1%3Fx%3D%C3%A9%26y?id=42"
+        );
+    }
+
+    #[test]
+    fn render_without_variables() {
+        assert_eq!(
+            render("{\"duration\":20}", &context(), Escape::None).unwrap(),
+            "{\"duration\":20}"
+        );
+    }
+
+    #[test]
+    fn render_step_responses() {
+        let mut context = context();
+        context.steps.insert(
+            "ad_server".to_string(),
+            Response {
+                status: 201,
+                body: r#"{"breaks": [{"id": "b-42", "duration": 30}]}"#.to_string(),
+            },
+        );
+        assert_eq!(
+            render(
+                "{{ steps.ad_server.status }} {{ steps.ad_server.body.breaks[0].id }} {{steps.ad_server.body['breaks'][0].duration}}",
+                &context,
+                Escape::None
+            )
+            .unwrap(),
+            "201 b-42 30"
+        );
+        assert!(render("{{ steps.ad_server.body.missing }}", &context, Escape::None).is_err());
+        assert!(render("{{ steps.ad_server.headers }}", &context, Escape::None).is_err());
+        assert!(render("{{ steps.other.status }}", &context, Escape::None).is_err());
+    }
+
+    #[test]
+    fn render_rejects_invalid_templates() {
+        assert!(render("{{unknown}}", &context(), Escape::None).is_err());
+        assert!(render("{{event_id", &context(), Escape::None).is_err());
+    }
+}
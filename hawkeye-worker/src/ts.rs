@@ -0,0 +1,242 @@
+//! Extracts the SCTE-35 sections carried in a MPEG transport stream.
+//!
+//! The PAT gives the PIDs of the PMTs, and the PMTs give the PIDs of the elementary streams of
+//! the SCTE-35 sections (stream type 0x86).
+//!
+//! See: ISO/IEC 13818-1, section 2.4 "Transport stream", and ANSI/SCTE 35, section 8.
+
+use log::info;
+use std::collections::{HashMap, HashSet};
+
+const PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0x0000;
+const PAT_TABLE_ID: u8 = 0x00;
+const PMT_TABLE_ID: u8 = 0x02;
+const SCTE35_TABLE_ID: u8 = 0xFC;
+const SCTE35_STREAM_TYPE: u8 = 0x86;
+
+/// Finds the SCTE-35 sections in the transport stream, pushed in chunks of any size.
+#[derive(Default)]
+pub struct Scte35Extractor {
+    /// Bytes of a packet split across chunks.
+    pending: Vec<u8>,
+    pmt_pids: HashSet<u16>,
+    scte35_pids: HashSet<u16>,
+    /// Sections being reassembled, by PID.
+    sections: HashMap<u16, Vec<u8>>,
+}
+
+impl Scte35Extractor {
+    /// Reads the packets of the chunk, returning the SCTE-35 sections they complete.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        self.pending.extend_from_slice(chunk);
+        let mut found = Vec::new();
+        let mut start = 0;
+        while self.pending.len() - start >= PACKET_SIZE {
+            if self.pending[start] != SYNC_BYTE {
+                // Lost the packet boundaries, look for the next sync byte
+                start += 1;
+                continue;
+            }
+            let packet = self.pending[start..start + PACKET_SIZE].to_vec();
+            self.read_packet(&packet, &mut found);
+            start += PACKET_SIZE;
+        }
+        self.pending.drain(..start);
+        found
+    }
+
+    fn read_packet(&mut self, packet: &[u8], found: &mut Vec<Vec<u8>>) {
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2]);
+        if pid != PAT_PID && !self.pmt_pids.contains(&pid) && !self.scte35_pids.contains(&pid) {
+            return;
+        }
+        let adaptation = (packet[3] >> 4) & 0x3;
+        let mut payload = &packet[4..];
+        if adaptation & 0x2 != 0 {
+            let len = payload[0] as usize;
+            if len + 1 > payload.len() {
+                return;
+            }
+            payload = &payload[len + 1..];
+        }
+        if adaptation & 0x1 == 0 || payload.is_empty() {
+            return;
+        }
+
+        if unit_start {
+            let pointer = payload[0] as usize;
+            if pointer + 1 > payload.len() {
+                self.sections.remove(&pid);
+                return;
+            }
+            // The bytes before the pointer finish the section started in earlier packets
+            let (end, start) = payload[1..].split_at(pointer);
+            if let Some(section) = self.sections.get_mut(&pid) {
+                section.extend_from_slice(end);
+            }
+            self.take_sections(pid, found);
+            self.sections.insert(pid, start.to_vec());
+        } else if let Some(section) = self.sections.get_mut(&pid) {
+            section.extend_from_slice(payload);
+        }
+        self.take_sections(pid, found);
+    }
+
+    /// Reads the complete sections buffered for the PID.
+    fn take_sections(&mut self, pid: u16, found: &mut Vec<Vec<u8>>) {
+        loop {
+            let buffer = match self.sections.get_mut(&pid) {
+                Some(buffer) => buffer,
+                None => return,
+            };
+            // Stuffing fills the rest of the packet after the last section
+            if buffer.first() == Some(&0xFF) {
+                self.sections.remove(&pid);
+                return;
+            }
+            if buffer.len() < 3 {
+                return;
+            }
+            let len = 3 + ((usize::from(buffer[1] & 0x0F) << 8) | usize::from(buffer[2]));
+            if buffer.len() < len {
+                return;
+            }
+            let section: Vec<u8> = buffer.drain(..len).collect();
+            self.read_section(pid, section, found);
+        }
+    }
+
+    fn read_section(&mut self, pid: u16, section: Vec<u8>, found: &mut Vec<Vec<u8>>) {
+        match section[0] {
+            PAT_TABLE_ID if pid == PAT_PID => {
+                // Programs of 4 bytes follow the header of 8 bytes, and the CRC ends the section
+                if section.len() < 12 {
+                    return;
+                }
+                for program in section[8..section.len() - 4].chunks_exact(4) {
+                    let number = u16::from_be_bytes([program[0], program[1]]);
+                    // Program 0 gives the PID of the network information
+                    if number != 0 {
+                        let pmt_pid = (u16::from(program[2] & 0x1F) << 8) | u16::from(program[3]);
+                        self.pmt_pids.insert(pmt_pid);
+                    }
+                }
+            }
+            PMT_TABLE_ID if self.pmt_pids.contains(&pid) => {
+                if section.len() < 16 {
+                    return;
+                }
+                let program_info_length =
+                    (usize::from(section[10] & 0x0F) << 8) | usize::from(section[11]);
+                let mut streams = section
+                    .get(12 + program_info_length..section.len() - 4)
+                    .unwrap_or_default();
+                while streams.len() >= 5 {
+                    let stream_type = streams[0];
+                    let stream_pid = (u16::from(streams[1] & 0x1F) << 8) | u16::from(streams[2]);
+                    let info_length =
+                        (usize::from(streams[3] & 0x0F) << 8) | usize::from(streams[4]);
+                    if stream_type == SCTE35_STREAM_TYPE && self.scte35_pids.insert(stream_pid) {
+                        info!("Found SCTE-35 cues in PID {}", stream_pid);
+                    }
+                    streams = streams.get(5 + info_length..).unwrap_or_default();
+                }
+            }
+            SCTE35_TABLE_ID if self.scte35_pids.contains(&pid) => found.push(section),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scte35::crc32_mpeg2;
+
+    const PMT_PID: u16 = 0x1000;
+    const SCTE35_PID: u16 = 0x1F0;
+
+    /// Ends the section with its length and CRC.
+    fn section(table_id: u8, body: &[u8]) -> Vec<u8> {
+        let mut section = vec![table_id, 0xB0, 0x00];
+        section.extend_from_slice(body);
+        let len = section.len() - 3 + 4;
+        section[1] |= (len >> 8) as u8;
+        section[2] = len as u8;
+        let crc = crc32_mpeg2(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    /// Splits the sections in packets of the PID, starting with a pointer field.
+    fn packets(pid: u16, sections: &[u8]) -> Vec<u8> {
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(sections);
+        let mut packets = Vec::new();
+        for (index, chunk) in payload.chunks(PACKET_SIZE - 4).enumerate() {
+            let unit_start = if index == 0 { 0x40 } else { 0x00 };
+            packets.push(SYNC_BYTE);
+            packets.push(unit_start | (pid >> 8) as u8);
+            packets.push(pid as u8);
+            packets.push(0x10 | (index & 0xF) as u8);
+            packets.extend_from_slice(chunk);
+            packets.resize(packets.len() + PACKET_SIZE - 4 - chunk.len(), 0xFF);
+        }
+        packets
+    }
+
+    fn program_tables() -> Vec<u8> {
+        let pat = section(
+            PAT_TABLE_ID,
+            &[0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00],
+        );
+        let pmt = section(
+            PMT_TABLE_ID,
+            &[
+                0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0,
+                0x00, // header, PCR PID and no info
+                0x1B, 0xE1, 0x00, 0xF0, 0x00, // H.264 video
+                0x86, 0xE1, 0xF0, 0xF0, 0x00, // SCTE-35
+            ],
+        );
+        let mut ts = packets(PAT_PID, &pat);
+        ts.append(&mut packets(PMT_PID, &pmt));
+        ts
+    }
+
+    #[test]
+    fn extracts_sections_of_scte35_pid() {
+        let cue = section(SCTE35_TABLE_ID, &[0x00; 10]);
+        let mut extractor = Scte35Extractor::default();
+        // Sections are ignored until the PMT gives the PID
+        assert!(extractor.push(&packets(SCTE35_PID, &cue)).is_empty());
+
+        let mut ts = program_tables();
+        ts.append(&mut packets(SCTE35_PID, &cue));
+        // Sections of other PIDs are ignored
+        ts.append(&mut packets(0x100, &cue));
+        assert_eq!(extractor.push(&ts), vec![cue]);
+    }
+
+    #[test]
+    fn reassembles_sections_across_packets_and_chunks() {
+        let cue = section(SCTE35_TABLE_ID, &[0xAB; 400]);
+        let other = section(SCTE35_TABLE_ID, &[0xCD; 20]);
+        let mut both = cue.clone();
+        both.extend_from_slice(&other);
+        // Garbage before the first packet
+        let mut ts = vec![0x00, 0x01];
+        ts.append(&mut program_tables());
+        ts.append(&mut packets(SCTE35_PID, &both));
+
+        let mut extractor = Scte35Extractor::default();
+        let mut found = Vec::new();
+        for chunk in ts.chunks(100) {
+            found.append(&mut extractor.push(chunk));
+        }
+        assert_eq!(found, vec![cue, other]);
+    }
+}
@@ -1,7 +1,16 @@
 // Based on https://gitlab.freedesktop.org/gstreamer/gstreamer-rs/-/blob/master/examples/src/bin/thumbnail.rs
 
-use crate::img_detector::SlateDetector;
-use crate::metrics::{FOUND_CONTENT_COUNTER, FOUND_SLATE_COUNTER, SIMILARITY_EXECUTION_COUNTER};
+use crate::audio::{AudioDetector, CHANNELS, SAMPLE_RATE};
+use crate::debounce::ModeDebouncer;
+use crate::img_detector::Detectors;
+use crate::metrics::{
+    FOUND_BLACK_COUNTER, FOUND_CONTENT_COUNTER, FOUND_CUE_COUNTER, FOUND_FROZEN_COUNTER,
+    FOUND_SLATE_BY_LABEL_COUNTER, FOUND_SLATE_COUNTER, SIMILARITY_EXECUTION_COUNTER,
+};
+use crate::queue::Queue;
+use crate::scte35::{self, SpliceInfoSection};
+use crate::signal::SignalMonitor;
+use crate::ts::Scte35Extractor;
 use color_eyre::Result;
 use concread::CowCell;
 use derive_more::{Display, Error};
@@ -9,12 +18,11 @@ use gst::gst_element_error;
 use gst::prelude::*;
 use gstreamer as gst;
 use gstreamer_app as gst_app;
-use hawkeye_core::models::{Codec, Container, VideoMode};
+use hawkeye_core::models::{Codec, Container, Protocol, Source, SrtMode, VideoMode};
 use lazy_static::lazy_static;
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 lazy_static! {
     pub(crate) static ref LATEST_FRAME: CowCell<Option<Vec<u8>>> = CowCell::new(None);
@@ -29,38 +37,134 @@ struct ErrorMessage {
     source: glib::Error,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Event {
     Terminate,
-    Mode(VideoMode),
+    /// Video mode found in a frame, with the label of the slate when one was found.
+    Mode(VideoMode, Option<String>),
 }
 
 pub fn create_pipeline(
-    detector: SlateDetector,
-    ingest_port: u32,
-    container: Container,
-    codec: Codec,
-    action_sink: Sender<Event>,
+    detectors: Detectors,
+    debouncer: ModeDebouncer,
+    signal: Option<Arc<Mutex<SignalMonitor>>>,
+    audio: Option<AudioDetector>,
+    scte35: Option<Scte35Extractor>,
+    source: &Source,
+    action_sink: Queue<Event>,
 ) -> Result<gst::Pipeline> {
-    let (width, height) = detector.required_image_size();
-
-    let pipeline_description = match (container, codec) {
-        (Container::MpegTs, Codec::H264) => format!(
-            "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)MP2T, payload=(int)33\" ! .recv_rtp_sink_0 rtpbin ! rtpmp2tdepay ! tsdemux ! h264parse ! avdec_h264 ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\" ! pngenc snapshot=false ! appsink name=sink",
-            ingest_port,
-            width,
-            height
+    let (width, height) = detectors.required_image_size();
+    let frame_interval = detectors.frame_interval();
+    let detectors = Mutex::new(detectors);
+    let debouncer = Mutex::new(debouncer);
+
+    let (caps, decoder) = match source.codec {
+        Codec::H264 => ("video/x-h264", "h264parse ! avdec_h264"),
+        Codec::H265 => ("video/x-h265", "h265parse ! avdec_h265"),
+    };
+
+    if scte35.is_some() && source.container != Container::MpegTs {
+        return Err(color_eyre::eyre::eyre!(
+            "SCTE-35 cues can only be read from a MPEG-TS container"
+        ));
+    }
+    if audio.is_some() && source.container == Container::RawVideo {
+        return Err(color_eyre::eyre::eyre!(
+            "Raw video payload has no audio to detect"
+        ));
+    }
+    // The demuxer pushes the audio and the video from a single thread, each branch gets its own
+    // so the video sink waiting for preroll doesn't keep the audio from prerolling
+    let decoder = if audio.is_some() {
+        format!("{} ! queue ! {}", caps, decoder)
+    } else {
+        decoder.to_string()
+    };
+
+    let input_description = match (&source.transport, source.container) {
+        (Protocol::Rtp, Container::MpegTs) => format!(
+            "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)MP2T, payload=(int)33\" ! .recv_rtp_sink_0 rtpbin ! rtpmp2tdepay ! {} ! {}",
+            listen_port(source)?,
+            demuxer(source.container, scte35.is_some())?,
+            decoder
         ),
-        (Container::RawVideo, Codec::H264) => format!(
-            "udpsrc port={} caps = \"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)H264, payload=(int)96\" ! rtph264depay ! decodebin ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\" ! pngenc snapshot=false ! appsink name=sink",
-            ingest_port,
-            width,
-            height
+        (Protocol::Rtp, Container::RawVideo) => {
+            let (encoding_name, depayloader) = match source.codec {
+                Codec::H264 => ("H264", "rtph264depay"),
+                Codec::H265 => ("H265", "rtph265depay"),
+            };
+            format!(
+                "udpsrc port={} caps = \"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string){}, payload=(int)96\" ! {} ! {}",
+                listen_port(source)?,
+                encoding_name,
+                depayloader,
+                decoder
+            )
+        }
+        (Protocol::Srt { .. }, Container::MpegTs) => format!(
+            "{} ! {} ! {}",
+            srt_source_description(source)?,
+            demuxer(source.container, scte35.is_some())?,
+            decoder
+        ),
+        (Protocol::Hls { url }, Container::MpegTs) | (Protocol::Hls { url }, Container::Fmp4) => {
+            format!(
+                "souphttpsrc location=\"{}\" ! hlsdemux ! {} ! {}",
+                url,
+                demuxer(source.container, scte35.is_some())?,
+                decoder
+            )
+        }
+        (Protocol::Http { url }, Container::MpegTs)
+        | (Protocol::Http { url }, Container::Fmp4) => format!(
+            "souphttpsrc location=\"{}\" is-live=true ! {} ! {}",
+            url,
+            demuxer(source.container, scte35.is_some())?,
+            decoder
+        ),
+        (Protocol::File { path }, Container::MpegTs)
+        | (Protocol::File { path }, Container::Fmp4) => format!(
+            "filesrc location=\"{}\" ! {} ! {}",
+            path,
+            demuxer(source.container, scte35.is_some())?,
+            decoder
         ),
-        (_, _) => {
-            return Err(color_eyre::eyre::eyre!("Container ({:?}) and Codec ({:?}) not available", container, codec));
+        (transport, container) => {
+            return Err(color_eyre::eyre::eyre!(
+                "Container ({:?}) and Codec ({:?}) not available for the {} transport",
+                container,
+                source.codec,
+                transport.name()
+            ));
         }
     };
+    let sampling = match frame_interval {
+        // Dropping the frames first saves scaling and encoding them
+        Some(interval) => format!(
+            " ! videorate drop-only=true ! video/x-raw,framerate=1000/{}",
+            interval
+        ),
+        None => String::new(),
+    };
+    let mut pipeline_description = format!(
+        "{}{} ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\" ! pngenc snapshot=false ! appsink name=sink",
+        input_description,
+        sampling,
+        width,
+        height
+    );
+    if audio.is_some() {
+        // The audio branches off the demuxer, converted to the format the detector measures
+        pipeline_description.push_str(&format!(
+            " demux. ! audio/mpeg ! queue ! decodebin ! audioconvert ! audioresample ! audio/x-raw,format=F32LE,layout=interleaved,rate={},channels={} ! appsink name=audio_sink sync=false",
+            SAMPLE_RATE,
+            CHANNELS
+        ));
+    }
+    if scte35.is_some() {
+        // The transport stream is teed before the demuxer, which drops the SCTE-35 sections
+        pipeline_description.push_str(" ts. ! queue ! appsink name=ts_sink sync=false");
+    }
 
     // Create our pipeline from a pipeline description string.
     debug!("Creating GStreamer Pipeline..");
@@ -78,6 +182,13 @@ pub fn create_pipeline(
     // Don't synchronize on the clock, we only want a snapshot asap.
     appsink.set_property("sync", &false)?;
 
+    if let Some(audio) = audio {
+        set_audio_callbacks(&pipeline, audio, action_sink.clone());
+    }
+    if let Some(scte35) = scte35 {
+        set_scte35_callbacks(&pipeline, scte35, action_sink.clone());
+    }
+
     // Getting data out of the appsink is done by setting callbacks on it.
     // The appsink will then call those handlers, as soon as data is available.
     appsink.set_callbacks(
@@ -115,7 +226,23 @@ pub fn create_pipeline(
                 // Prevents reading twice.
                 let local_buffer = buffer.to_vec();
 
-                let is_match = detector.is_match(local_buffer.as_slice());
+                if let Some(signal) = signal.as_ref() {
+                    if signal.lock().unwrap().frame() {
+                        // The mode before the signal was lost is not assumed to continue
+                        debouncer.lock().unwrap().reset();
+                    }
+                }
+
+                let detection = detectors.lock().unwrap().detect(local_buffer.as_slice());
+                let (mode, slate_label) = detection.map_err(|err| {
+                    gst_element_error!(
+                        appsink,
+                        gst::ResourceError::Failed,
+                        ("Failed to decode frame: {}", err)
+                    );
+
+                    gst::FlowError::Error
+                })?;
 
                 {
                     // Save latest image bytes
@@ -125,17 +252,35 @@ pub fn create_pipeline(
                     write_txn.commit();
                 }
 
-                if is_match {
-                    debug!("Found slate image in video stream!");
-                    FOUND_SLATE_COUNTER.inc();
-                    action_sink.send(Event::Mode(VideoMode::Slate)).unwrap();
-                } else {
-                    FOUND_CONTENT_COUNTER.inc();
-                    action_sink.send(Event::Mode(VideoMode::Content)).unwrap();
-                    debug!("Did not find slate..");
+                match mode {
+                    VideoMode::Slate => {
+                        debug!("Found slate image {:?} in video stream!", slate_label);
+                        FOUND_SLATE_COUNTER.inc();
+                        FOUND_SLATE_BY_LABEL_COUNTER
+                            .with_label_values(&[slate_label.as_deref().unwrap_or("")])
+                            .inc();
+                    }
+                    VideoMode::Black => {
+                        debug!("Found black frame in video stream!");
+                        FOUND_BLACK_COUNTER.inc();
+                    }
+                    VideoMode::Frozen => {
+                        debug!("Found frozen frame in video stream!");
+                        FOUND_FROZEN_COUNTER.inc();
+                    }
+                    _ => {
+                        FOUND_CONTENT_COUNTER.inc();
+                        debug!("Did not find slate..");
+                    }
                 }
                 SIMILARITY_EXECUTION_COUNTER.inc();
 
+                // Only confirmed modes reach the actions
+                let confirmed = debouncer.lock().unwrap().update(mode, slate_label);
+                if let Some((mode, slate_label)) = confirmed {
+                    action_sink.push(Event::Mode(mode, slate_label));
+                }
+
                 Ok(gst::FlowSuccess::Ok)
             })
             .build(),
@@ -144,10 +289,176 @@ pub fn create_pipeline(
     Ok(pipeline)
 }
 
+/// Measures the audio samples and pushes the modes of the audio to the actions.
+fn set_audio_callbacks(pipeline: &gst::Pipeline, audio: AudioDetector, action_sink: Queue<Event>) {
+    let audio_sink = pipeline
+        .get_by_name("audio_sink")
+        .expect("Audio sink element not found")
+        .downcast::<gst_app::AppSink>()
+        .expect("Audio sink element is expected to be an appsink!");
+    let audio = Mutex::new(audio);
+
+    audio_sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let buffer_ref = sample.get_buffer().ok_or_else(|| {
+                    gst_element_error!(
+                        appsink,
+                        gst::ResourceError::Failed,
+                        ("Failed to get buffer from audio appsink")
+                    );
+
+                    gst::FlowError::Error
+                })?;
+                let buffer = buffer_ref.map_readable().map_err(|_| {
+                    gst_element_error!(
+                        appsink,
+                        gst::ResourceError::Failed,
+                        ("Failed to map audio buffer readable")
+                    );
+
+                    gst::FlowError::Error
+                })?;
+                let samples: Vec<f32> = buffer
+                    .as_slice()
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+
+                // The audio is not debounced, the detector already waits for the silence to last
+                for mode in audio.lock().unwrap().detect(&samples) {
+                    action_sink.push(Event::Mode(mode, None));
+                }
+
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+}
+
+/// Reads the SCTE-35 sections of the transport stream and pushes the cues to the actions.
+fn set_scte35_callbacks(
+    pipeline: &gst::Pipeline,
+    scte35: Scte35Extractor,
+    action_sink: Queue<Event>,
+) {
+    let ts_sink = pipeline
+        .get_by_name("ts_sink")
+        .expect("Transport stream sink element not found")
+        .downcast::<gst_app::AppSink>()
+        .expect("Transport stream sink element is expected to be an appsink!");
+    let scte35 = Mutex::new(scte35);
+
+    ts_sink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let buffer_ref = sample.get_buffer().ok_or_else(|| {
+                    gst_element_error!(
+                        appsink,
+                        gst::ResourceError::Failed,
+                        ("Failed to get buffer from transport stream appsink")
+                    );
+
+                    gst::FlowError::Error
+                })?;
+                let buffer = buffer_ref.map_readable().map_err(|_| {
+                    gst_element_error!(
+                        appsink,
+                        gst::ResourceError::Failed,
+                        ("Failed to map transport stream buffer readable")
+                    );
+
+                    gst::FlowError::Error
+                })?;
+
+                for section in scte35.lock().unwrap().push(buffer.as_slice()) {
+                    let section = match SpliceInfoSection::decode(&section) {
+                        Ok(section) => section,
+                        Err(err) => {
+                            warn!(
+                                "Ignoring SCTE-35 section {}: {}",
+                                hex::encode(&section),
+                                err
+                            );
+                            continue;
+                        }
+                    };
+                    debug!("Found SCTE-35 section: {:?}", section);
+                    // Heartbeats and other segmentation types signal no mode
+                    if let Some(mode) = scte35::cue_mode(&section) {
+                        FOUND_CUE_COUNTER.with_label_values(&[mode.name()]).inc();
+                        action_sink.push(Event::Mode(mode, None));
+                    }
+                }
+
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+}
+
+/// Demuxer extracting the elementary streams from the container, named `demux` so the audio can
+/// branch off it. The transport stream is teed as `ts` first when its SCTE-35 cues are read.
+fn demuxer(container: Container, scte35: bool) -> Result<&'static str> {
+    match container {
+        Container::MpegTs if scte35 => Ok("tee name=ts ! queue ! tsdemux name=demux"),
+        Container::MpegTs => Ok("tsdemux name=demux"),
+        // Handles both fragmented MP4 and CMAF
+        Container::Fmp4 => Ok("qtdemux name=demux"),
+        Container::RawVideo => Err(color_eyre::eyre::eyre!(
+            "Raw video payload has no container to demux"
+        )),
+    }
+}
+
+fn listen_port(source: &Source) -> Result<u32> {
+    source
+        .listen_port()
+        .ok_or_else(|| color_eyre::eyre::eyre!("Source is missing the ingest port"))
+}
+
+/// Builds the `srtsrc` element description for the SRT caller and listener modes.
+fn srt_source_description(source: &Source) -> Result<String> {
+    let (mode, address, passphrase, latency) = match &source.transport {
+        Protocol::Srt {
+            mode,
+            address,
+            passphrase,
+            latency,
+        } => (mode, address, passphrase, latency),
+        _ => return Err(color_eyre::eyre::eyre!("Source transport is not SRT")),
+    };
+
+    let uri = match mode {
+        SrtMode::Caller => format!(
+            "srt://{}?mode=caller",
+            address
+                .as_ref()
+                .ok_or_else(|| color_eyre::eyre::eyre!("SRT caller requires an address"))?
+        ),
+        SrtMode::Listener => format!("srt://:{}?mode=listener", listen_port(source)?),
+    };
+
+    let mut description = format!("srtsrc uri=\"{}\"", uri);
+    if let Some(latency) = latency {
+        description.push_str(&format!(" latency={}", latency));
+    }
+    if let Some(passphrase) = passphrase {
+        // The watcher's secrets are resolved before the pipeline is created
+        description.push_str(&format!(
+            " passphrase=\"{}\"",
+            passphrase.expose()?.replace('"', "\\\"")
+        ));
+    }
+    Ok(description)
+}
+
 pub fn main_loop(
     pipeline: gst::Pipeline,
     running: Arc<AtomicBool>,
-    action_sink: Sender<Event>,
+    action_sink: Queue<Event>,
 ) -> Result<()> {
     pipeline.set_state(gst::State::Paused)?;
 
@@ -191,7 +502,7 @@ pub fn main_loop(
     }
 
     info!("Stopping pipeline gracefully!");
-    action_sink.send(Event::Terminate)?;
+    action_sink.push(Event::Terminate);
     pipeline.set_state(gst::State::Null)?;
 
     Ok(())